@@ -1,3 +0,0 @@
-fn main() {
-    println!("cargo:rustc-env=RUST_TEST_NOCAPTURE=1");
-}
@@ -0,0 +1,1862 @@
+// ------------------------------------
+// API NOTES
+//
+// 1) All functions must use owned passing (no references) for thread safety (stop deadlocks)
+// 2) The original class (Thingy) must have a constructor ("new" function)
+// 3) The worker is created by calling <original_class_name>Worker::new()
+// 4) Methods are not allowed to be non-blocking and have a return value (no promises)
+// 5) Generic methods (including impl Trait arguments) are sent as boxed closures,
+//    so all of their type parameters must be Send + 'static (no lifetime parameters)
+// 6) "stop_thread", "exec", "query", "send_after", "send_at", "upgrade", "queue_len", "is_alive", "thread_id",
+//    "messages_processed", "uptime", "metrics", "new_manual", "start_recording", "stop_recording", "replay",
+//    "handle_request", "serve", "serve_unix", "snapshot", "restore", "open", and "compact" are generated for every
+//    worker (the last ten with #[worker(record)], #[worker(serde)], #[worker(remote)], #[worker(snapshot)], or
+//    #[worker(event_log)]), "with_routing", "workers", and "pool_size" for every worker pool, and "shard_count" and
+//    "shard" for every sharded worker, so they can't be public methods. Neither can "<method>_then" and
+//    "<method>_reply_to" for a blocking method, or "broadcast_<method>" for a non-blocking one (see 16) and 19)).
+// 7) Periodic methods (#[periodic(every_ms = N)]) only take self and are run by the worker between messages
+//    send_after()/send_at() closures are counted once (as exec, in messages_processed and the metrics) when they run
+// 8) Lifecycle hooks (#[on_start], #[on_stop], #[on_idle]) only take self, and #[on_panic] also takes the
+//    panic payload. #[on_panic] runs when a message, a periodic method, #[on_start], or #[on_idle] panics, and the
+//    panic is resumed after it returns, so #[on_stop] is not called.
+// 9) #[worker(debug)] or NANO_SERVICES_EXPAND=<dir> writes the generated code and a summary of each method to
+//    <dir>/<original_class_name>Worker_<file>_<line>.rs, where <file> is the path of the source file with #[worker]
+//    (with "_" for anything but letters and digits), and <line> its line
+//    (#[worker(debug)] defaults to <target dir>/nano_services).
+//    Cargo doesn't track the env var, so touch the file (or cargo clean) to regenerate the report.
+// 10) <original_class_name>Worker::new_manual() doesn't spawn a thread. Messages wait in the mailbox until the
+//     returned driver handles them with step()/step_all(), and periodic methods and lifecycle hooks aren't run.
+//     Blocking calls have to come from another thread (or use the driver's state() instead), see 18).
+// 11) #[worker(record)] needs the "record" feature, and the args of every public method must be Serialize and
+//     Deserialize. Messages sent while recording are logged (as JSON lines) in the order they reach the mailbox.
+//     replay() sends the logged method calls again (ignoring return values), but not stop_thread, or the messages
+//     that can't be rebuilt from the log (exec, query, send_after/send_at, upgrade, compact, generic and #[streaming]
+//     methods, and <method>_then/_reply_to), which it counts in Replay::skipped.
+// 12) #[worker(serde)] needs the "serde" feature, and generates <original_class_name>WorkerRequest and
+//     <original_class_name>WorkerResponse, so the args and return types of every non-generic public method must be
+//     Serialize and Deserialize.
+//     Blocking requests carry a correlation_id (instead of the reply channel) that is copied to their response,
+//     and <original_class_name>Worker::handle_request() sends a request to the worker and waits for its response.
+// 13) #[worker(remote)] needs the "remote" feature, and implies #[worker(serde)]. <original_class_name>Worker::serve()
+//     (TCP) or serve_unix() (Unix socket) serves the worker, and Remote<original_class_name>Worker::connect() or
+//     connect_unix() returns a proxy with the same non-generic public methods. The served worker ignores stop_thread
+//     requests, so only its own process can stop it. Requests and responses are length-prefixed JSON frames (of at
+//     most nano_services::remote::MAX_FRAME_LEN bytes).
+//     Blocking methods wait for their response, and IO errors panic like a stopped worker would. Dropping the last
+//     clone of a proxy closes its connection.
+// 14) <original_class_name>Worker::spawn_named("name", ..) also registers the worker in nano_services::Registry,
+//     so it can be found with Registry::get::<<original_class_name>Worker>("name") until its thread stops.
+//     It panics (after stopping the new worker) if a running worker already has the name.
+// 15) #[streaming] (or #[streaming(buffer = N)]) methods take a nano_services::Sink<Item> as their last argument, and
+//     the worker method returns a nano_services::Stream<Item> instead. Sink::send() waits while N items (default 16)
+//     are unread, and fails once the Stream is dropped. The worker can't handle other messages while it waits.
+// 16) Blocking methods also get a non-blocking <method>_then(.., |result| ..), which runs the closure with the
+//     result on the worker thread (so it shouldn't wait on the same worker). It can send the result on to
+//     another worker, for example with other.exec(move |other| ..), or with <method>_reply_to(.., other, Other::method)
+//     (which calls the method on other with the result, so its argument has to match the return type).
+// 17) With the "deadlock_detection" feature, a blocking call (including query) panics with the chain of waiting
+//     threads if the worker it would wait on is already waiting on the calling thread (directly or through other
+//     workers). The check takes a global lock.
+// 18) Blocking calls (including query) from the worker's own thread always panic straight away, since they would
+//     wait forever behind the message that's running. With new_manual(), that's the thread that created the worker.
+// 19) <original_class_name>WorkerPool::new(n, |index| (<constructor args>,)) spawns n workers behind one handle with
+//     the same methods. Each call goes to one worker (see nano_services::Routing, set with with_routing()), and
+//     non-blocking methods also get broadcast_<method>(..), which sends a clone of the args to every worker.
+// 20) Marking one argument of a method with #[shard_key] adds the method to <original_class_name>WorkerShards
+//     (created like a pool), which sends each call to the worker picked by hashing that argument (so it must be
+//     Hash). Calls with the same key are always handled by the same worker, in order. Methods without a
+//     #[shard_key] argument can be called on a specific worker, through shard(&key) or workers().
+// 21) #[worker(consumers = N)] spawns N threads (each with its own instance, so the constructor args must be Clone)
+//     that take messages from one shared mailbox, so each message is handled by whichever consumer is free. Messages
+//     are no longer handled in the order they were sent, and blocking calls between consumers are allowed (18) and
+//     17) don't apply). The JoinHandle returned by new() waits for every consumer, and thread_id() is the first one's.
+// 22) #[worker(snapshot)] needs the "snapshot" feature, and the class must be Serialize and Deserialize. snapshot() is
+//     a blocking call (handled in order, like query) that returns the serialized state, or the error (without
+//     stopping the worker) if the state can't be serialized as JSON. <original_class_name>Worker::restore(&snapshot)
+//     spawns a worker with that state instead of calling new().
+//     It can't be used with several consumers (21), since they each have their own state.
+// 23) #[worker(event_log)] needs the "event_log" feature, and the class and the args of its &mut self methods must
+//     be Serialize and Deserialize. <original_class_name>Worker::open(path, <constructor args>) rebuilds
+//     the state by calling those methods again with every call in the log (starting from new(), or the last
+//     snapshot), and then logs each new call (synced to disk) before sending it, so sending is slower. An incomplete
+//     last line (from a crash while it was written) is dropped. compact() replaces the calls that the worker has
+//     handled with a snapshot of its state. Changes made through exec() aren't logged (until the next compact()), and
+//     generic or #[streaming] methods, periodic methods, and lifecycle hooks (apart from #[on_panic]) can't take
+//     &mut self. Blocking methods are logged when they're sent (including <method>_then), and their return values
+//     are ignored when the log is read again.
+// 24) upgrade(|old| new) replaces the worker's state, in order with the other messages in its mailbox (so the
+//     handles and queued messages stay valid). If the closure panics, the worker stops without running #[on_panic] or
+//     #[on_stop], since the old state has been moved into it. Upgrades aren't generated with several consumers, or
+//     with #[worker(event_log)] (since open() couldn't rebuild the upgraded state from the log).
+// ------------------------------------
+
+use convert_case::{Case, Casing};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use proc_macro::*;
+use proc_macro_error::*;
+use syn::fold::Fold;
+use syn::*;
+
+// Must use this until "proc_macro_quote" becomes stable
+// At which point replace to_token_stream() with quote!(#method).to_string();
+// Or convert this whole thing into using Tokens entirely
+// This can be done slowly by still using to_token_stream in intermediate steps
+use syn::__private::ToTokens;
+
+// Methods that every worker generates, so the class can't expose its own versions of them
+const RESERVED_WORKER_METHODS: &[&str] = &[
+    "stop_thread", "exec", "query", "send_after", "send_at", "upgrade", "queue_len", "is_alive", "thread_id",
+    "messages_processed", "uptime", "metrics", "start_recording", "stop_recording", "replay", "handle_request",
+    "serve", "serve_unix", "handle_remote_request", "spawn_named", "worker_handle", "worker_dispatch", "with_routing", "workers", "pool_size",
+    "pool_worker", "shard_count", "shard", "worker_spawn", "snapshot", "restore", "new_manual", "open", "compact",
+];
+
+// Whether the worker loop records per-method metrics (see nano_services::metrics)
+const METRICS_ENABLED: bool = cfg!(feature = "metrics");
+
+// Whether the worker runs each message inside a span (linked to the span it was sent from)
+const TRACING_ENABLED: bool = cfg!(feature = "tracing");
+
+// Whether blocking calls check the wait-for graph of workers before waiting (see nano_services::deadlock)
+const DEADLOCK_DETECTION_ENABLED: bool = cfg!(feature = "deadlock_detection");
+
+// How many items a #[streaming] method can send before it waits for the caller
+const DEFAULT_STREAMING_BUFFER: usize = 16;
+
+// Attributes for the optional methods that the worker loop calls at each point of its life
+const LIFECYCLE_HOOKS: &[&str] = &["on_start", "on_stop", "on_idle", "on_panic"];
+
+fn params_to_arg_types_string(method: &ImplItemMethod) -> String {
+    method.sig.inputs.pairs().fold(String::new(), |cur, next| {
+        let symbols = match next.value() {
+            FnArg::Receiver(_) => "".to_string(),
+            FnArg::Typed(ty) => ty.ty.to_token_stream().to_string(),
+        };
+
+        if cur.is_empty() {
+            symbols
+        } else {
+            cur + ", " + &symbols
+        }
+    })
+}
+
+fn params_to_arg_names_string(method: &ImplItemMethod) -> String {
+    method.sig.inputs.pairs().fold(String::new(), |cur, next| {
+        let symbols = match next.value() {
+            FnArg::Receiver(_) => "".to_string(),
+            FnArg::Typed(ty) => match &*ty.pat {
+                Pat::Ident(ident) => ident.ident.to_string(),
+                _ => "INVALID_TYPE_IN_FUNCTION_ARG_NAMES".to_string(),
+            },
+        };
+
+        if cur.is_empty() {
+            symbols
+        } else {
+            cur + ", " + &symbols
+        }
+    })
+}
+
+fn returns_to_arg_types_string(method: &ImplItemMethod) -> Option<String> {
+    match &method.sig.output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) => Some(ty.to_token_stream().to_string()),
+    }
+}
+
+// The worker only sends messages, so its methods never need to mutably borrow it
+fn handle_signature_string(method: &ImplItemMethod) -> String {
+    let mut sig = method.sig.clone();
+    if let Some(FnArg::Receiver(receiver)) = sig.inputs.first_mut() {
+        *receiver = parse_quote!(&self);
+    }
+    sig.to_token_stream().to_string()
+}
+
+// The fields of the method's variant in the recorded message enum ("name: Type, ...")
+fn params_to_named_fields_string(method: &ImplItemMethod) -> String {
+    method.sig.inputs.pairs().fold(String::new(), |cur, next| {
+        let symbols = match next.value() {
+            FnArg::Receiver(_) => return cur,
+            FnArg::Typed(ty) => match &*ty.pat {
+                Pat::Ident(ident) => format!("{}: {}", ident.ident, ty.ty.to_token_stream()),
+                _ => "INVALID_TYPE_IN_FUNCTION_ARG_NAMES".to_string(),
+            },
+        };
+
+        if cur.is_empty() {
+            symbols
+        } else {
+            cur + ", " + &symbols
+        }
+    })
+}
+
+// Logs the message to the recorder, which stays locked until the message is sent (nothing unless recording)
+fn record_message_string(record: bool, method_name: &str, arg_names: Option<&str>) -> String {
+    if !record {
+        return String::new();
+    }
+
+    let message = match arg_names {
+        None => format!("\"{method_name}\""),
+        Some(arg_names) => {
+            let fields = arg_names
+                .split(", ")
+                .filter(|x| !x.is_empty())
+                .map(|x| format!("\"{x}\": {x}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{{\"{method_name}\": {{{fields}}}}}")
+        }
+    };
+    format!("let mut worker_record = self.recorder.lock(); worker_record.write(|| nano_services::serde_json::json!({message}));")
+}
+
+// Appends the call to the event log, which stays locked until the message is sent (nothing unless the method is
+// logged, or if the worker wasn't opened with a log)
+fn event_message_string(logged: bool, method_name: &str, arg_names: &str) -> String {
+    if !logged {
+        return String::new();
+    }
+
+    let fields = arg_names
+        .split(", ")
+        .filter(|x| !x.is_empty())
+        .map(|x| format!("\"{x}\": {x}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("let mut worker_event = self.event_log.lock(); worker_event.append(|| nano_services::serde_json::json!({{\"{method_name}\": {{{fields}}}}}));")
+}
+
+// Fails fast when a blocking call comes from the worker's own thread, since its message would queue behind the
+// handler that's making the call (nothing with several consumers, since another one can handle it)
+fn same_thread_check_string(consumers: usize, class_name: &str, method_name: &str) -> String {
+    if consumers > 1 {
+        return String::new();
+    }
+
+    format!("if std::thread::current().id() == self.thread_id {{ panic!(\"{class_name}Worker::{method_name} is a blocking call from {class_name}Worker's own thread, so it would wait forever. Please use a non-blocking method (or {method_name}_then) instead, or call the method on the worker's state directly.\"); }}")
+}
+
+// Registers a blocking call in the wait-for graph until it returns (nothing unless deadlock detection is enabled,
+// or with several consumers, since the call doesn't wait on one particular thread)
+fn deadlock_wait_string(consumers: usize, class_name: &str, method_name: &str) -> String {
+    if !DEADLOCK_DETECTION_ENABLED || consumers > 1 {
+        return String::new();
+    }
+
+    format!("let _worker_wait = nano_services::deadlock::wait_for(self.thread_id, \"{class_name}::{method_name}\");")
+}
+
+// Calls a periodic method or lifecycle hook on the worker's state, running #[on_panic] (if there is one) before
+// resuming a panic, like for a message
+fn hook_call_string(object_name: &str, method_name: &str, on_panic: Option<&String>) -> String {
+    match on_panic {
+        None => format!("{object_name}.{method_name}();"),
+        Some(on_panic) => format!("if let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {object_name}.{method_name}())) {{ {object_name}.{on_panic}(&*payload); std::panic::resume_unwind(payload); }}"),
+    }
+}
+
+// Forwards a worker method from the pool (to the next worker), and from the sharded worker (to the worker for the
+// method's #[shard_key] argument, if it has one)
+fn push_forwarder(pool_output: &mut Vec<String>, shards_output: &mut Vec<String>, shard_key: Option<&str>, signature: &str, call: &str) {
+    pool_output.push(format!("pub {signature} {{"));
+    pool_output.push(format!("self.pool_worker().{call}"));
+    pool_output.push("}".to_string());
+    if let Some(shard_key) = shard_key {
+        shards_output.push(format!("pub {signature} {{"));
+        shards_output.push(format!("self.shard(&{shard_key}).{call}"));
+        shards_output.push("}".to_string());
+    }
+}
+
+// Removes the #[shard_key] attributes from the method's arguments (attribute macros can't be used on arguments, so
+// they can't be left in the output), and returns the arguments that had one
+fn take_shard_keys(method: &mut ImplItemMethod) -> Vec<PatType> {
+    let mut shard_keys = Vec::new();
+    for arg in method.sig.inputs.iter_mut() {
+        let FnArg::Typed(arg) = arg else {
+            continue;
+        };
+        let attr_count = arg.attrs.len();
+        arg.attrs.retain(|x| !x.path.is_ident("shard_key"));
+        if arg.attrs.len() != attr_count {
+            shard_keys.push(arg.clone());
+        }
+    }
+    shard_keys
+}
+
+fn method_has_attribute(method: &ImplItemMethod, attribute: &str) -> bool {
+    method
+        .attrs
+        .iter()
+        .any(|x| x.path.segments.iter().any(|x| x.ident == attribute))
+}
+
+fn is_method_blocking(method: &ImplItemMethod) -> bool {
+    method_has_attribute(method, "blocking_method")
+}
+
+fn is_method_mutating(method: &ImplItemMethod) -> bool {
+    matches!(method.sig.inputs.first(), Some(FnArg::Receiver(receiver)) if receiver.mutability.is_some())
+}
+
+// The span the worker enters while handling a message (nothing unless tracing is enabled)
+fn tracing_span_string(class_name: &str, method_name: &str, arg_names: &str) -> String {
+    if !TRACING_ENABLED {
+        return String::new();
+    }
+
+    let fields = arg_names
+        .split(", ")
+        .filter(|x| !x.is_empty())
+        .fold(String::new(), |cur, next| {
+            cur + &format!(", {next} = ?(&nano_services::TraceArg(&{next})).trace_arg()")
+        });
+    format!("let _worker_span = nano_services::tracing::info_span!(\"{class_name}::{method_name}\"{fields}).entered();")
+}
+
+fn method_periodic_interval(method: &ImplItemMethod) -> Option<u64> {
+    let attr = method
+        .attrs
+        .iter()
+        .find(|x| x.path.segments.iter().any(|x| x.ident == "periodic"))?;
+
+    let every_ms = match attr.parse_meta() {
+        Ok(Meta::List(list)) => list.nested.iter().find_map(|nested| match nested {
+            NestedMeta::Meta(Meta::NameValue(value)) if value.path.is_ident("every_ms") => {
+                match &value.lit {
+                    Lit::Int(lit) => lit.base10_parse::<u64>().ok().filter(|x| *x > 0),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }),
+        _ => None,
+    };
+
+    if every_ms.is_none() {
+        emit_error!(attr, "Invalid periodic attribute. Please use #[periodic(every_ms = <milliseconds>)] with a non-zero interval.");
+    }
+    every_ms
+}
+
+// The buffer size of a #[streaming] method's Sink (None if the method isn't streaming)
+fn method_streaming_buffer(method: &ImplItemMethod) -> Option<usize> {
+    let attr = method
+        .attrs
+        .iter()
+        .find(|x| x.path.segments.iter().any(|x| x.ident == "streaming"))?;
+
+    let buffer = match attr.parse_meta() {
+        Ok(Meta::Path(_)) => Some(DEFAULT_STREAMING_BUFFER),
+        Ok(Meta::List(list)) => list.nested.iter().find_map(|nested| match nested {
+            NestedMeta::Meta(Meta::NameValue(value)) if value.path.is_ident("buffer") => {
+                match &value.lit {
+                    Lit::Int(lit) => lit.base10_parse::<usize>().ok().filter(|x| *x > 0),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }),
+        _ => None,
+    };
+
+    if buffer.is_none() {
+        emit_error!(attr, "Invalid streaming attribute. Please use #[streaming] or #[streaming(buffer = <items>)] with a non-zero buffer.");
+    }
+    Some(buffer.unwrap_or(DEFAULT_STREAMING_BUFFER))
+}
+
+// The item type of the Sink<T> that a #[streaming] method takes as its last argument
+fn streaming_item_type(method: &ImplItemMethod) -> Option<Type> {
+    let Some(FnArg::Typed(arg)) = method.sig.inputs.last() else {
+        return None;
+    };
+    let Type::Path(path) = &*arg.ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first() {
+        Some(GenericArgument::Type(ty)) if segment.ident == "Sink" && args.args.len() == 1 => Some(ty.clone()),
+        _ => None,
+    }
+}
+
+fn is_method_static(method: &ImplItemMethod) -> bool {
+    method.sig.inputs.pairs().all(|next| match next.value() {
+        FnArg::Receiver(_) => false,
+        FnArg::Typed(_) => true,
+    })
+}
+
+fn is_method_generic(method: &ImplItemMethod, class_name: &str) -> bool {
+    let mut eraser = GenericEraser::new(class_name);
+    eraser.fold_signature(method.sig.clone());
+    !method.sig.generics.params.is_empty() || eraser.impl_trait_found
+}
+
+fn generics_to_turbofish_string(method: &ImplItemMethod) -> String {
+    let params = method.sig.generics.params.iter().fold(String::new(), |cur, next| {
+        let symbols = match next {
+            GenericParam::Type(param) => param.ident.to_string(),
+            GenericParam::Const(param) => param.ident.to_string(),
+            GenericParam::Lifetime(_) => return cur,
+        };
+
+        if cur.is_empty() {
+            symbols
+        } else {
+            cur + ", " + &symbols
+        }
+    });
+
+    if params.is_empty() {
+        params
+    } else {
+        format!("::<{params}>")
+    }
+}
+
+// Generic methods can't be WorkerFuncs variants, so they are sent to the worker as boxed closures.
+// Everything the closure captures has to cross the thread boundary, so the handle version of the
+// signature replaces Self with the class, only borrows the handle, and requires Send + 'static of
+// every type parameter and impl Trait argument.
+struct GenericEraser {
+    class_path: Path,
+    impl_trait_found: bool,
+}
+
+impl GenericEraser {
+    fn new(class_name: &str) -> Self {
+        GenericEraser {
+            class_path: parse_str(class_name).expect("Invalid class name"),
+            impl_trait_found: false,
+        }
+    }
+
+    fn erase_signature(&mut self, sig: &Signature) -> Signature {
+        let mut sig = self.fold_signature(sig.clone());
+        sig.generics.params.iter_mut().for_each(|param| {
+            if let GenericParam::Type(param) = param {
+                param.bounds.push(parse_quote!(Send));
+                param.bounds.push(parse_quote!('static));
+            }
+        });
+        sig
+    }
+}
+
+impl Fold for GenericEraser {
+    fn fold_receiver(&mut self, _receiver: Receiver) -> Receiver {
+        parse_quote!(&self)
+    }
+
+    fn fold_type_path(&mut self, ty: TypePath) -> TypePath {
+        let mut ty = fold::fold_type_path(self, ty);
+        if ty.qself.is_none() && ty.path.segments.first().is_some_and(|x| x.ident == "Self") {
+            let mut path = self.class_path.clone();
+            path.segments.extend(ty.path.segments.into_iter().skip(1));
+            ty.path = path;
+        }
+        ty
+    }
+
+    fn fold_type_impl_trait(&mut self, ty: TypeImplTrait) -> TypeImplTrait {
+        let mut ty = fold::fold_type_impl_trait(self, ty);
+        ty.bounds.push(parse_quote!(Send));
+        ty.bounds.push(parse_quote!('static));
+        self.impl_trait_found = true;
+        ty
+    }
+}
+
+// TODO: JPB: (feature) Make everything except the worker methods private (including the original class?)
+// TODO: JPB: (feature) Make the original class's constructor create the worker?
+// TODO: JPB: (feature) Add the ability to use this method on traits as well
+// TODO: JPB: (feature) Add publish/subscribe feature (maybe as another proc_macro_aatribute)
+// TODO: JPB: (QOL) Change "Worker" to "NanoService"
+#[proc_macro_error]
+#[proc_macro_attribute]
+pub fn worker(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let args = parse_worker_args(&args);
+    let mut input = parse_macro_input!(item as ItemImpl);
+
+    // The #[shard_key] argument of each method (removed from the impl, which is output again below)
+    let shard_keys: HashMap<String, Vec<PatType>> = input.items.iter_mut().filter_map(|item| match item {
+      ImplItem::Method(method) => Some((method.sig.ident.to_string(), take_shard_keys(method))),
+      _ => None,
+    }).collect();
+
+    let Type::Path(path) = &*input.self_ty else {
+        abort!(input.self_ty, "Invalid type for impl name");
+    };
+
+    let class_name = path.path.segments.pairs().fold(String::new(), |cur, next| {
+        cur + &next.value().ident.to_string()
+    });
+    let object_name = class_name.to_case(Case::Camel);
+
+    // Generate Includes
+    let includes_output = [
+        "use crossbeam_channel;".to_string(),
+        "use futures;".to_string()];
+
+    // Generate WorkerFuncs Enum
+    let mut funcs_enum_output = vec![
+        "enum WorkerFuncs {".to_string(),
+        "WorkerQuit(),".to_string(),
+        format!("WorkerExec(Box<dyn FnOnce(&mut {class_name}) + Send>),"),
+        "WorkerSendAt(std::time::Instant, nano_services::Envelope<WorkerFuncs>),".to_string()];
+
+    // Upgrades replace the state, so they're only generated when there's one (see the worker loop below), and not
+    // when the state is rebuilt from an event log, which couldn't replay them
+    let upgradable = args.consumers == 1 && !args.event_log;
+    if upgradable {
+      funcs_enum_output.push(format!("WorkerUpgrade(Box<dyn FnOnce({class_name}) -> {class_name} + Send>),"));
+    }
+
+    // Generate Struct Worker
+    let mut worker_struct_output = vec![
+        "#[derive(Clone, Debug)]".to_string(),
+        format!("pub(crate) struct {class_name}Worker {{"),
+        "send: crossbeam_channel::Sender<nano_services::Envelope<WorkerFuncs>>,".to_string(),
+        "thread_id: std::thread::ThreadId,".to_string(),
+        "started: std::time::Instant,".to_string(),
+        "alive: std::sync::Weak<()>,".to_string(),
+        "processed: std::sync::Arc<std::sync::atomic::AtomicU64>,".to_string()];
+
+    // Generate Impl Worker
+    let mut worker_impl_new_intro = Vec::new();
+    let mut worker_impl_new_match = Vec::new();
+    let mut worker_impl_new_outro = Vec::new();
+    let record_release = if args.record { "drop(worker_record);" } else { "" };
+    let mut worker_impl_output = vec![
+        format!("impl {class_name}Worker {{"),
+        "pub fn stop_thread(&self) {".to_string(),
+        record_message_string(args.record, "stop_thread", None),
+        // Every consumer stops after the first WorkerQuit that it takes from the mailbox
+        format!("for _ in 0..{} {{", args.consumers),
+        "self.send.send(nano_services::Envelope::new(WorkerFuncs::WorkerQuit())).expect(\"Failed to send stop_thread command\");".to_string(),
+        "}".to_string(),
+        "}".to_string(),
+        format!("pub fn exec<F: FnOnce(&mut {class_name}) + Send + 'static>(&self, func: F) {{"),
+        record_message_string(args.record, "exec", None),
+        "self.send.send(nano_services::Envelope::new(WorkerFuncs::WorkerExec(Box::new(func)))).expect(\"Failed to send exec to Worker\");".to_string(),
+        "}".to_string(),
+        format!("pub fn query<R: Send + 'static, F: FnOnce(&{class_name}) -> R + Send + 'static>(&self, func: F) -> R {{"),
+        same_thread_check_string(args.consumers, &class_name, "query"),
+        "let (send_ret, recv_ret) = futures::channel::oneshot::channel::<Box<R>>();".to_string(),
+        format!("let func = move |{object_name}: &mut {class_name}| if send_ret.send(Box::new(func({object_name}))).is_err() {{ panic!(\"Failed to send return value of query in Worker\") }};"),
+        record_message_string(args.record, "query", None),
+        "self.send.send(nano_services::Envelope::new(WorkerFuncs::WorkerExec(Box::new(func)))).expect(\"Failed to send query to Worker\");".to_string(),
+        record_release.to_string(),
+        deadlock_wait_string(args.consumers, &class_name, "query"),
+        "match futures::executor::block_on(async move { recv_ret.await }) {".to_string(),
+        "Ok(x) => *x,".to_string(),
+        "Err(_) => panic!(\"Error on async await of result in query\"),".to_string(),
+        "}".to_string(),
+        "}".to_string(),
+        format!("pub fn send_after<F: FnOnce(&mut {class_name}) + Send + 'static>(&self, delay: std::time::Duration, func: F) {{"),
+        "self.send_at(std::time::Instant::now() + delay, func);".to_string(),
+        "}".to_string(),
+        format!("pub fn send_at<F: FnOnce(&mut {class_name}) + Send + 'static>(&self, time: std::time::Instant, func: F) {{"),
+        record_message_string(args.record, "send_at", None),
+        "self.send.send(nano_services::Envelope::new(WorkerFuncs::WorkerSendAt(time, nano_services::Envelope::new(WorkerFuncs::WorkerExec(Box::new(func)))))).expect(\"Failed to send send_at to Worker\");".to_string(),
+        "}".to_string(),
+        "pub fn queue_len(&self) -> usize {".to_string(),
+        "self.send.len()".to_string(),
+        "}".to_string(),
+        "pub fn is_alive(&self) -> bool {".to_string(),
+        "self.alive.strong_count() > 0".to_string(),
+        "}".to_string(),
+        "pub fn thread_id(&self) -> std::thread::ThreadId {".to_string(),
+        "self.thread_id".to_string(),
+        "}".to_string(),
+        "pub fn messages_processed(&self) -> u64 {".to_string(),
+        "self.processed.load(std::sync::atomic::Ordering::Relaxed)".to_string(),
+        "}".to_string(),
+        "pub fn uptime(&self) -> std::time::Duration {".to_string(),
+        "self.started.elapsed()".to_string(),
+        "}".to_string()];
+    if upgradable {
+      worker_impl_output.extend([
+        format!("pub fn upgrade<F: FnOnce({class_name}) -> {class_name} + Send + 'static>(&self, func: F) {{"),
+        record_message_string(args.record, "upgrade", None),
+        "self.send.send(nano_services::Envelope::new(WorkerFuncs::WorkerUpgrade(Box::new(func)))).expect(\"Failed to send upgrade to Worker\");".to_string(),
+        "}".to_string()]);
+    }
+    if args.snapshot {
+      if args.consumers > 1 {
+        emit_error!(input.self_ty, "#[worker(snapshot)] can't be used with several consumers, since they each have their own state.");
+      }
+      worker_impl_output.extend([
+        "pub fn snapshot(&self) -> std::io::Result<Vec<u8>> {".to_string(),
+        format!("self.query(|{object_name}: &{class_name}| nano_services::snapshot::to_bytes({object_name}))"),
+        "}".to_string()]);
+    }
+
+    // Generate Metrics (names of the WorkerFuncs variants and the recorder shared with the worker)
+    let mut funcs_names_output = vec![
+        "impl WorkerFuncs {".to_string(),
+        "fn method_name(&self) -> &'static str {".to_string(),
+        "match self {".to_string(),
+        "WorkerFuncs::WorkerQuit() => \"stop_thread\",".to_string(),
+        "WorkerFuncs::WorkerExec(..) => \"exec\",".to_string(),
+        "WorkerFuncs::WorkerSendAt(..) => \"send_at\",".to_string()];
+    if upgradable {
+      funcs_names_output.push("WorkerFuncs::WorkerUpgrade(..) => \"upgrade\",".to_string());
+    }
+    if METRICS_ENABLED {
+      worker_struct_output.push("metrics: std::sync::Arc<nano_services::metrics::MetricsRecorder>,".to_string());
+      worker_impl_output.push("pub fn metrics(&self) -> nano_services::metrics::WorkerMetrics {".to_string());
+      worker_impl_output.push("self.metrics.snapshot()".to_string());
+      worker_impl_output.push("}".to_string());
+    }
+
+    // Generate Recorder (messages that can be replayed are logged with their args, the rest by name)
+    let mut recorded_enum_output = Vec::new();
+    let mut recorded_replay_output = Vec::new();
+    let mut recorded_skipped = vec![
+      format!("{class_name}WorkerMessage::Exec"),
+      format!("{class_name}WorkerMessage::Query"),
+      format!("{class_name}WorkerMessage::SendAt"),
+      format!("{class_name}WorkerMessage::Upgrade")];
+    if args.record {
+      worker_struct_output.push("recorder: std::sync::Arc<nano_services::record::Recorder>,".to_string());
+      worker_impl_output.extend([
+        "pub fn start_recording(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {".to_string(),
+        "self.recorder.start(path)".to_string(),
+        "}".to_string(),
+        "pub fn stop_recording(&self) -> std::io::Result<()> {".to_string(),
+        "self.recorder.stop()".to_string(),
+        "}".to_string()]);
+      recorded_enum_output.extend([
+        "#[derive(nano_services::serde::Deserialize)]".to_string(),
+        "#[serde(crate = \"nano_services::serde\")]".to_string(),
+        format!("pub(crate) enum {class_name}WorkerMessage {{"),
+        "#[serde(rename = \"stop_thread\")]".to_string(),
+        "StopThread,".to_string(),
+        "#[serde(rename = \"exec\")]".to_string(),
+        "Exec,".to_string(),
+        "#[serde(rename = \"query\")]".to_string(),
+        "Query,".to_string(),
+        "#[serde(rename = \"send_at\")]".to_string(),
+        "SendAt,".to_string(),
+        "#[serde(rename = \"upgrade\")]".to_string(),
+        "Upgrade,".to_string()]);
+      if args.event_log {
+        recorded_enum_output.extend(["#[serde(rename = \"compact\")]".to_string(), "Compact,".to_string()]);
+        recorded_skipped.push(format!("{class_name}WorkerMessage::Compact"));
+      }
+    }
+
+    // Generate Event Log (the &mut self methods are logged, so the state can be rebuilt by calling them again)
+    let mut event_enum_output = Vec::new();
+    let mut event_apply_output = Vec::new();
+    if args.event_log {
+      if args.consumers > 1 {
+        emit_error!(input.self_ty, "#[worker(event_log)] can't be used with several consumers, since they each have their own state.");
+      }
+      worker_struct_output.push("event_log: std::sync::Arc<nano_services::event_log::EventLog>,".to_string());
+      event_enum_output.extend([
+        "#[derive(nano_services::serde::Deserialize)]".to_string(),
+        "#[serde(crate = \"nano_services::serde\")]".to_string(),
+        format!("pub(crate) enum {class_name}WorkerEvent {{"),
+        "#[serde(rename = \"snapshot\")]".to_string(),
+        format!("Snapshot({class_name}),")]);
+      event_apply_output.push(format!("{class_name}WorkerEvent::Snapshot(state) => {object_name} = state,"));
+    }
+    worker_struct_output.push("}".to_string());
+
+    // Generate Serde Messages (requests for every non-generic method, and responses for the blocking ones)
+    let mut request_enum_output = Vec::new();
+    let mut response_enum_output = Vec::new();
+    let mut handle_request_output = Vec::new();
+    if args.serde {
+      request_enum_output.extend([
+        "#[derive(nano_services::serde::Serialize, nano_services::serde::Deserialize)]".to_string(),
+        "#[serde(crate = \"nano_services::serde\")]".to_string(),
+        format!("pub(crate) enum {class_name}WorkerRequest {{"),
+        "#[serde(rename = \"stop_thread\")]".to_string(),
+        "StopThread,".to_string()]);
+      response_enum_output.extend([
+        "#[derive(nano_services::serde::Serialize, nano_services::serde::Deserialize)]".to_string(),
+        "#[serde(crate = \"nano_services::serde\")]".to_string(),
+        format!("pub(crate) enum {class_name}WorkerResponse {{")]);
+      handle_request_output.extend([
+        format!("pub fn handle_request(&self, request: {class_name}WorkerRequest) -> Option<{class_name}WorkerResponse> {{"),
+        "match request {".to_string(),
+        format!("{class_name}WorkerRequest::StopThread => {{ self.stop_thread(); None }},")]);
+    }
+
+    // Generate Remote Worker (a proxy that sends requests to a worker served in another process, which can't stop it)
+    let mut remote_output = Vec::new();
+    let mut correlated_output = Vec::new();
+    if args.remote {
+      worker_impl_output.extend([
+        "pub fn serve(&self, addr: impl std::net::ToSocketAddrs) -> std::io::Result<(std::thread::JoinHandle<()>, std::net::SocketAddr)> {".to_string(),
+        "let listener = std::net::TcpListener::bind(addr)?;".to_string(),
+        "let local_addr = listener.local_addr()?;".to_string(),
+        "let worker = self.clone();".to_string(),
+        "Ok((std::thread::spawn(move || nano_services::remote::serve_tcp(listener, move |request| worker.handle_remote_request(request))), local_addr))".to_string(),
+        "}".to_string(),
+        "#[cfg(unix)]".to_string(),
+        "pub fn serve_unix(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<std::thread::JoinHandle<()>> {".to_string(),
+        "let listener = std::os::unix::net::UnixListener::bind(path)?;".to_string(),
+        "let worker = self.clone();".to_string(),
+        "Ok(std::thread::spawn(move || nano_services::remote::serve_unix(listener, move |request| worker.handle_remote_request(request))))".to_string(),
+        "}".to_string(),
+        format!("fn handle_remote_request(&self, request: {class_name}WorkerRequest) -> Option<{class_name}WorkerResponse> {{"),
+        "match request {".to_string(),
+        format!("{class_name}WorkerRequest::StopThread => None,"),
+        "request => self.handle_request(request),".to_string(),
+        "}".to_string(),
+        "}".to_string()]);
+      remote_output.extend([
+        "#[derive(Clone)]".to_string(),
+        format!("pub(crate) struct Remote{class_name}Worker {{"),
+        format!("client: std::sync::Arc<nano_services::remote::Client<{class_name}WorkerRequest, {class_name}WorkerResponse>>,"),
+        "}".to_string(),
+        "#[allow(unreachable_patterns)]".to_string(),
+        format!("impl Remote{class_name}Worker {{"),
+        "pub fn connect(addr: impl std::net::ToSocketAddrs) -> std::io::Result<Self> {".to_string(),
+        "Ok(Self { client: std::sync::Arc::new(nano_services::remote::connect_tcp(addr)?) })".to_string(),
+        "}".to_string(),
+        "#[cfg(unix)]".to_string(),
+        "pub fn connect_unix(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {".to_string(),
+        "Ok(Self { client: std::sync::Arc::new(nano_services::remote::connect_unix(path)?) })".to_string(),
+        "}".to_string()]);
+      correlated_output.extend([
+        format!("impl nano_services::remote::Correlated for {class_name}WorkerResponse {{"),
+        "fn correlation_id(&self) -> u64 {".to_string(),
+        "match *self {".to_string()]);
+    }
+
+    // Check that the class has a public "new" method
+    let mut new_exists = false;
+    let mut pub_new_exists = false;
+    for item in &input.items {
+        if let ImplItem::Method(method) = item {
+            let method_is_new = method.sig.ident == "new";
+            let method_is_public = matches!(method.vis, Visibility::Public(_));
+            new_exists |= method_is_new;
+            pub_new_exists |= method_is_new && method_is_public;
+            if pub_new_exists {
+                break;
+            }
+        }
+    }
+    if !new_exists {
+        emit_error!(input, "The \"{class_name}\" class does not have a public \"new\" method. All #[worker] classes must have a public \"new\" method. Please create a public \"new\" method.");
+    } else if !pub_new_exists {
+        emit_error!(input, "The \"{class_name}\" class has a private \"new\" method. All #[worker] classes must have a public \"new\" method. Please make your \"new\" method public.");
+    }
+
+    // Periodic methods and their intervals (in milliseconds), and lifecycle hooks
+    let mut periodic_methods = Vec::new();
+    let mut hook_methods = HashMap::new();
+
+    // Methods of the worker pool and the sharded worker (forwarded to one of their workers)
+    let mut pool_new_output = Vec::new();
+    let mut pool_output = Vec::new();
+    let mut shards_output = Vec::new();
+    let mut pool_args_type = String::new();
+    let mut constructor_params = String::new();
+    let mut constructor_arg_names = String::new();
+
+    // Summary of each public method for the expansion report
+    let mut method_summaries = Vec::new();
+
+    // Names generated for the public methods (<method>_then and <method>_reply_to for the blocking ones, and
+    // broadcast_<method> for the others), which other public methods can't have either
+    let derived_method_names: Vec<String> = input.items.iter().filter_map(|item| match item {
+      ImplItem::Method(method) if matches!(method.vis, Visibility::Public(_)) && !is_method_static(method) && !is_method_generic(method, &class_name) => Some(method),
+      _ => None,
+    }).flat_map(|method| {
+      let method_name = method.sig.ident.to_string();
+      if is_method_blocking(method) {
+        vec![format!("{method_name}_then"), format!("{method_name}_reply_to")]
+      } else if !method_has_attribute(method, "streaming") {
+        vec![format!("broadcast_{method_name}")]
+      } else {
+        Vec::new()
+      }
+    }).collect();
+
+    // Walk through original Impl functions
+    input.items.iter().for_each(|item| {
+      let ImplItem::Method(method) = item else {
+        abort!(item, "Non-method found inside impl block. Only methods are allowed in impl blocks.");
+      };
+
+      // Lifecycle hooks are run by the worker loop, so they don't need to be public
+      if let Some(hook) = LIFECYCLE_HOOKS.iter().find(|hook| method_has_attribute(method, hook)) {
+        let hook_arg_count = if *hook == "on_panic" { 2 } else { 1 };
+        if is_method_static(method) || method.sig.inputs.len() != hook_arg_count || method.sig.output != ReturnType::Default {
+          let hook_args = if *hook == "on_panic" { "self and the panic payload (&(dyn std::any::Any + Send))" } else { "self" };
+          emit_error!(method.sig, "Lifecycle hook {class_name}::{} must only take {hook_args} as arguments and can't have a return type.", method.sig.ident);
+        }
+        if args.event_log && *hook != "on_panic" && is_method_mutating(method) {
+          emit_error!(method.sig, "Lifecycle hook {}::{} takes &mut self, but its changes can't be logged by #[worker(event_log)]. Please take &self instead.", class_name, method.sig.ident);
+        }
+        if hook_methods.insert(*hook, method.sig.ident.to_string()).is_some() {
+          emit_error!(method.sig.ident, "The \"{class_name}\" class has more than one #[{hook}] method. Only one is allowed.");
+        }
+      }
+
+      // Periodic methods are run by the worker loop, so they don't need to be public
+      if let Some(every_ms) = method_periodic_interval(method) {
+        if is_method_static(method) || method.sig.inputs.len() != 1 || method.sig.output != ReturnType::Default {
+          emit_error!(method.sig, "Periodic method {class_name}::{} must only take self as an argument and can't have a return type.", method.sig.ident);
+        }
+        if args.event_log && is_method_mutating(method) {
+          emit_error!(method.sig, "Periodic method {class_name}::{} takes &mut self, but its changes can't be logged by #[worker(event_log)]. Please take &self instead.", method.sig.ident);
+        }
+        periodic_methods.push((method.sig.ident.to_string(), every_ms));
+      }
+
+      if let Visibility::Public(_) = method.vis { // Only expose public functions
+        let method_name = method.sig.ident.to_string();
+        let method_signature = handle_signature_string(method);
+        let method_params = method.sig.inputs.to_token_stream().to_string();
+        let enum_name = method_name.to_case(Case::UpperCamel);
+        let method_arg_names = params_to_arg_names_string(method);
+        let method_arg_types = params_to_arg_types_string(method);
+        let method_return_type = returns_to_arg_types_string(method);
+        let method_return_type_str = match &method_return_type {
+          None => "()",
+          Some(return_type) => return_type,
+        };
+
+        let mut enum_arg_types = method_arg_types.clone();
+        let mut enum_arg_names = method_arg_names.clone();
+
+        let method_is_blocking = is_method_blocking(method);
+        let method_is_static = is_method_static(method);
+        let method_is_generic = is_method_generic(method, &class_name);
+        let method_is_constructor = method_name == "new";
+
+        // Check that the method has at most one #[shard_key] argument (that's a plain name, so it can be hashed)
+        let method_shard_keys = shard_keys.get(&method_name).map(Vec::as_slice).unwrap_or_default();
+        if let Some(arg) = method_shard_keys.get(1) {
+          emit_error!(arg, "Method {}::{} has more than one #[shard_key] argument. Only one is allowed.", class_name, method_name);
+        }
+        if let (Some(arg), true) = (method_shard_keys.first(), method_is_static) {
+          emit_error!(arg, "Method {}::{} is static, so it can't have a #[shard_key] argument.", class_name, method_name);
+        }
+        let shard_key = method_shard_keys.first().and_then(|arg| match &*arg.pat {
+          Pat::Ident(ident) => Some(ident.ident.to_string()),
+          _ => None,
+        });
+
+        // Expansion Report
+        let method_variant = if method_is_static { "-".to_string() } else { enum_name.clone() };
+        method_summaries.push(format!(
+          "// {method_name:<24} {:<9} {:<7} {method_variant}",
+          if method_is_blocking { "yes" } else { "no" },
+          if method_is_static { "yes" } else { "no" }));
+
+        // Check for methods that would clash with the generated worker methods
+        if !method_is_static && (RESERVED_WORKER_METHODS.contains(&method_name.as_str()) || derived_method_names.contains(&method_name)) {
+          emit_error!(method.sig.ident, "Method {}::{} has the same name as a generated {}Worker method. Please rename it.", class_name, method_name, class_name);
+        }
+
+        // Check for methods that are non-blocking and have a return type
+        if !method_is_static && !method_is_blocking && method_return_type.is_some() {
+          emit_error!(method.sig, "Method {class_name}::{method_name} is a non-blocking method, but has a return type ({method_return_type_str}). This is not allowed.");
+        }
+
+        // Check that streaming methods send their items through a Sink<Item> (their last argument)
+        let streaming_buffer = method_streaming_buffer(method);
+        let method_is_streaming = streaming_buffer.is_some();
+        // Calls of &mut self methods are logged by #[worker(event_log)], so open() can call them again
+        let method_is_logged = args.event_log && !method_is_static && !method_is_generic && !method_is_streaming && is_method_mutating(method);
+        let streaming_item = streaming_item_type(method);
+        if method_is_streaming && (method_is_static || method_is_blocking || method_is_generic) {
+          emit_error!(method.sig, "Streaming method {}::{} can't be static, blocking, or generic.", class_name, method_name);
+        } else if method_is_streaming && streaming_item.is_none() {
+          emit_error!(method.sig, "Streaming method {}::{} must take a nano_services::Sink<Item> as its last argument.", class_name, method_name);
+        }
+
+        // Check that open() can rebuild every change to the state from the event log
+        if args.event_log && !method_is_static && is_method_mutating(method) && (method_is_generic || method_is_streaming) {
+          emit_error!(method.sig, "Method {}::{} is a generic or streaming &mut self method, so its calls can't be logged by #[worker(event_log)]. Please take &self instead.", class_name, method_name);
+        }
+
+        // Generate generic methods as boxed closures that are run by the worker
+        if method_is_generic && !method_is_static {
+          method.sig.generics.lifetimes().for_each(|param| {
+            emit_error!(param, "Method {class_name}::{method_name} has the lifetime parameter {}. Generic methods are sent to the worker as closures, so they can't borrow anything. Please use owned types instead.", param.lifetime);
+          });
+
+          let erased_signature = GenericEraser::new(&class_name).erase_signature(&method.sig);
+          let erased_return_type = match &erased_signature.output {
+            ReturnType::Default => "()".to_string(),
+            ReturnType::Type(_, ty) => ty.to_token_stream().to_string(),
+          };
+          if let ReturnType::Type(_, ty) = &method.sig.output {
+            let mut eraser = GenericEraser::new(&class_name);
+            eraser.fold_type(*ty.clone());
+            if eraser.impl_trait_found {
+              emit_error!(ty, "Method {class_name}::{method_name} returns an impl Trait type ({}). The worker can't name this type to send it back. Please return a concrete type or a Box<dyn Trait> instead.", ty.to_token_stream());
+            }
+          }
+          let method_call = format!("{object_name}.{method_name}{}({method_arg_names})", generics_to_turbofish_string(method));
+
+          funcs_enum_output.push(format!("{enum_name}(Box<dyn FnOnce(&mut {class_name}) + Send>),"));
+          funcs_names_output.push(format!("WorkerFuncs::{enum_name}(..) => \"{method_name}\","));
+
+          worker_impl_new_match.push(format!("WorkerFuncs::{enum_name}(func) => {{ {} func({object_name}) }},", tracing_span_string(&class_name, &method_name, "")));
+
+          if args.record {
+            recorded_enum_output.push(format!("#[serde(rename = \"{method_name}\")]"));
+            recorded_enum_output.push(format!("{enum_name},"));
+            recorded_skipped.push(format!("{class_name}WorkerMessage::{enum_name}"));
+          }
+
+          worker_impl_output.push(format!("pub {} {{", erased_signature.to_token_stream()));
+          if method_is_blocking {
+            worker_impl_output.push(same_thread_check_string(args.consumers, &class_name, &method_name));
+            worker_impl_output.push(format!("let (send_ret, recv_ret) = futures::channel::oneshot::channel::<Box<{erased_return_type}>>();"));
+            worker_impl_output.push(format!("let func = move |{object_name}: &mut {class_name}| if send_ret.send(Box::new({method_call})).is_err() {{ panic!(\"Failed to send return value of {enum_name} in Worker\") }};"));
+          } else {
+            worker_impl_output.push(format!("let func = move |{object_name}: &mut {class_name}| {method_call};"));
+          }
+          worker_impl_output.push(record_message_string(args.record, &method_name, None));
+          worker_impl_output.push(format!("self.send.send(nano_services::Envelope::new(WorkerFuncs::{enum_name}(Box::new(func)))).expect(\"Failed to send {enum_name} to Worker\");"));
+          if method_is_blocking {
+            worker_impl_output.push(record_release.to_string());
+            worker_impl_output.push(deadlock_wait_string(args.consumers, &class_name, &method_name));
+            worker_impl_output.push("match futures::executor::block_on(async move { recv_ret.await }) {".to_string());
+            worker_impl_output.push("Ok(x) => *x,".to_string());
+            worker_impl_output.push(format!("Err(_) => panic!(\"Error on async await of result in {method_name}\"),"));
+            worker_impl_output.push("}".to_string());
+          }
+          worker_impl_output.push("}".to_string());
+
+          // Generate Worker Pool and Sharded Worker
+          let erased_signature = erased_signature.to_token_stream().to_string();
+          let method_call = format!("{method_name}{}({method_arg_names})", generics_to_turbofish_string(method));
+          push_forwarder(&mut pool_output, &mut shards_output, shard_key.as_deref(), &erased_signature, &method_call);
+          return;
+        }
+
+        if !method_is_static {
+          funcs_names_output.push(format!("WorkerFuncs::{enum_name}(..) => \"{method_name}\","));
+        }
+
+        // Generate WorkerFuncs Enum
+        if !method_is_static {
+          if method_is_blocking {
+            enum_arg_types = format!("futures::channel::oneshot::Sender<Box<{method_return_type_str}>>, {method_arg_types}");
+            enum_arg_names = format!("send_ret, {method_arg_names}");
+          };
+
+          funcs_enum_output.push(format!("{enum_name}({enum_arg_types}),"));
+          if method_is_blocking {
+            funcs_enum_output.push(format!("{enum_name}Then(Box<dyn FnOnce({method_return_type_str}) + Send>, {method_arg_types}),"));
+            funcs_names_output.push(format!("WorkerFuncs::{enum_name}Then(..) => \"{method_name}\","));
+          }
+        }
+
+        // Generate Impl ThingyWorker
+        if method_is_constructor {
+          // The state is made by worker_state (once per consumer), so new() and restore() can share the worker thread
+          let state_bound = if args.consumers > 1 { "FnMut" } else { "FnOnce" };
+          worker_impl_new_intro.push(format!("fn worker_spawn(mut worker_state: impl {state_bound}() -> {class_name}) -> (std::thread::JoinHandle<()>, Self) {{"));
+          worker_impl_new_intro.push("let (send_func, recv_func) = crossbeam_channel::unbounded::<nano_services::Envelope<WorkerFuncs>>();".to_string());
+          worker_impl_new_intro.push("let worker_alive = std::sync::Arc::new(());".to_string());
+          worker_impl_new_intro.push("let alive = std::sync::Arc::downgrade(&worker_alive);".to_string());
+          worker_impl_new_intro.push("let processed = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));".to_string());
+          worker_impl_new_intro.push("let started = std::time::Instant::now();".to_string());
+          if METRICS_ENABLED {
+            worker_impl_new_intro.push("let metrics = std::sync::Arc::new(nano_services::metrics::MetricsRecorder::default());".to_string());
+          }
+          if args.consumers > 1 {
+            // Each consumer has its own state, and takes the next message from the shared mailbox when it's free
+            worker_impl_new_intro.push(format!("let mut worker_consumers = Vec::with_capacity({});", args.consumers));
+            worker_impl_new_intro.push(format!("for _ in 0..{} {{", args.consumers));
+            worker_impl_new_intro.push(format!("let mut {object_name} = worker_state();"));
+            worker_impl_new_intro.push("let recv_func = recv_func.clone();".to_string());
+            worker_impl_new_intro.push("let worker_alive = std::sync::Arc::clone(&worker_alive);".to_string());
+          } else {
+            worker_impl_new_intro.push(format!("let mut {object_name} = worker_state();"));
+          }
+          worker_impl_new_intro.push("let worker_processed = std::sync::Arc::clone(&processed);".to_string());
+          if METRICS_ENABLED {
+            worker_impl_new_intro.push("let worker_metrics = std::sync::Arc::clone(&metrics);".to_string());
+          }
+          worker_impl_new_intro.push("let handle = std::thread::spawn(move || {".to_string());
+          worker_impl_new_intro.push("let _worker_alive = worker_alive;".to_string());
+          worker_impl_new_intro.push("let mut worker_timers: Vec<(std::time::Instant, nano_services::Envelope<WorkerFuncs>)> = Vec::new();".to_string());
+
+          worker_impl_new_match.push("WorkerFuncs::WorkerQuit() => return false,".to_string());
+          worker_impl_new_match.push(format!("WorkerFuncs::WorkerExec(func) => {{ {} func({object_name}) }},", tracing_span_string(&class_name, "exec", "")));
+          worker_impl_new_match.push("WorkerFuncs::WorkerSendAt(..) => unreachable!(\"Delayed messages are scheduled by worker_handle\"),".to_string());
+          if upgradable {
+            worker_impl_new_match.push("WorkerFuncs::WorkerUpgrade(_) => unreachable!(\"Upgrades are handled by the worker loop\"),".to_string());
+          }
+
+          let mut worker_fields = if METRICS_ENABLED { "send: send_func, thread_id, started, alive, processed, metrics" } else { "send: send_func, thread_id, started, alive, processed" }.to_string();
+          if args.record {
+            worker_fields += ", recorder: std::sync::Arc::default()";
+          }
+          if args.event_log {
+            worker_fields += ", event_log: std::sync::Arc::default()";
+          }
+          worker_impl_new_outro.push("});".to_string());
+          if args.consumers > 1 {
+            // The returned handle joins every consumer (and resumes the first panic, if any of them panicked)
+            worker_impl_new_outro.push("worker_consumers.push(handle);".to_string());
+            worker_impl_new_outro.push("}".to_string());
+            worker_impl_new_outro.push("drop(worker_alive);".to_string());
+            worker_impl_new_outro.push("let thread_id = worker_consumers[0].thread().id();".to_string());
+            worker_impl_new_outro.push("let handle = std::thread::spawn(move || {".to_string());
+            worker_impl_new_outro.push("let worker_panics: Vec<_> = worker_consumers.into_iter().filter_map(|x| x.join().err()).collect();".to_string());
+            worker_impl_new_outro.push("if let Some(payload) = worker_panics.into_iter().next() {".to_string());
+            worker_impl_new_outro.push("std::panic::resume_unwind(payload);".to_string());
+            worker_impl_new_outro.push("}".to_string());
+            worker_impl_new_outro.push("});".to_string());
+          } else {
+            worker_impl_new_outro.push("let thread_id = handle.thread().id();".to_string());
+          }
+          worker_impl_new_outro.push(format!("(handle, Self {{{worker_fields}}})"));
+          worker_impl_new_outro.push("}".to_string());
+          if args.consumers > 1 {
+            let consumer_args = method_arg_names.split(", ").filter(|x| !x.is_empty()).map(|x| format!("{x}.clone()")).collect::<Vec<_>>().join(", ");
+            worker_impl_new_outro.push(format!("pub fn new({method_params}) -> (std::thread::JoinHandle<()>, Self) {{"));
+            worker_impl_new_outro.push(format!("Self::worker_spawn(move || {class_name}::new({consumer_args}))"));
+          } else {
+            worker_impl_new_outro.push(format!("pub fn new({method_params}) -> (std::thread::JoinHandle<()>, Self) {{"));
+            worker_impl_new_outro.push(format!("Self::worker_spawn(move || {class_name}::new({method_arg_names}))"));
+          }
+          worker_impl_new_outro.push("}".to_string());
+
+          // Generate Snapshots (restore() reads the snapshot before spawning, so a bad one is an error instead of a panic)
+          if args.snapshot && args.consumers == 1 {
+            worker_impl_new_outro.push("pub fn restore(snapshot: &[u8]) -> std::io::Result<(std::thread::JoinHandle<()>, Self)> {".to_string());
+            worker_impl_new_outro.push(format!("let state: {class_name} = nano_services::snapshot::from_bytes(snapshot)?;"));
+            worker_impl_new_outro.push("Ok(Self::worker_spawn(move || state))".to_string());
+            worker_impl_new_outro.push("}".to_string());
+          }
+
+          // Generate Worker Pool (each worker is constructed with the args returned for its index)
+          constructor_params = method_params.clone();
+          constructor_arg_names = method_arg_names.clone();
+          pool_args_type = if method_arg_types.is_empty() { "()".to_string() } else { format!("({method_arg_types},)") };
+          let pool_args_names = if method_arg_names.is_empty() { "()".to_string() } else { format!("({method_arg_names},)") };
+          pool_new_output.extend([
+            format!("pub fn new(size: usize, mut args: impl FnMut(usize) -> {pool_args_type}) -> (Vec<std::thread::JoinHandle<()>>, Self) {{"),
+            format!("assert!(size > 0, \"{class_name}WorkerPool needs at least one worker\");"),
+            "let (handles, workers) = (0..size).map(|index| {".to_string(),
+            format!("let {pool_args_names} = args(index);"),
+            format!("{class_name}Worker::new({method_arg_names})"),
+            "}).unzip();".to_string(),
+            "let pool = Self { workers: std::sync::Arc::new(workers), next: std::sync::Arc::default(), routing: nano_services::Routing::default() };".to_string(),
+            "(handles, pool)".to_string(),
+            "}".to_string()]);
+
+          // Generate the named constructor (registers the worker until its thread stops)
+          worker_impl_new_outro.push(format!("pub fn spawn_named(worker_name: &str, {method_params}) -> (std::thread::JoinHandle<()>, Self) {{"));
+          worker_impl_new_outro.push(format!("let (handle, worker) = Self::new({method_arg_names});"));
+          worker_impl_new_outro.push("if !nano_services::Registry::register(worker_name, worker.clone(), worker.alive.clone()) {".to_string());
+          worker_impl_new_outro.push("worker.stop_thread();".to_string());
+          worker_impl_new_outro.push("panic!(\"A worker named {:?} is already running\", worker_name);".to_string());
+          worker_impl_new_outro.push("}".to_string());
+          worker_impl_new_outro.push("(handle, worker)".to_string());
+          worker_impl_new_outro.push("}".to_string());
+
+          // Generate the manual constructor (messages are only handled when the driver steps)
+          worker_impl_new_outro.push(format!("pub fn new_manual({method_params}) -> ({class_name}WorkerDriver, Self) {{"));
+          worker_impl_new_outro.push("let (send_func, recv_func) = crossbeam_channel::unbounded::<nano_services::Envelope<WorkerFuncs>>();".to_string());
+          worker_impl_new_outro.push(format!("let {object_name} = {class_name}::new({method_arg_names});"));
+          worker_impl_new_outro.push("let worker_alive = std::sync::Arc::new(());".to_string());
+          worker_impl_new_outro.push("let alive = std::sync::Arc::downgrade(&worker_alive);".to_string());
+          worker_impl_new_outro.push("let processed = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));".to_string());
+          worker_impl_new_outro.push("let started = std::time::Instant::now();".to_string());
+          worker_impl_new_outro.push("let thread_id = std::thread::current().id();".to_string());
+          if METRICS_ENABLED {
+            worker_impl_new_outro.push("let metrics = std::sync::Arc::new(nano_services::metrics::MetricsRecorder::default());".to_string());
+          }
+          worker_impl_new_outro.push(format!("let driver = {class_name}WorkerDriver {{"));
+          worker_impl_new_outro.push(format!("state: Some({object_name}),"));
+          worker_impl_new_outro.push("recv: recv_func,".to_string());
+          worker_impl_new_outro.push("timers: Vec::new(),".to_string());
+          worker_impl_new_outro.push("processed: std::sync::Arc::clone(&processed),".to_string());
+          if METRICS_ENABLED {
+            worker_impl_new_outro.push("metrics: std::sync::Arc::clone(&metrics),".to_string());
+          }
+          worker_impl_new_outro.push("alive: Some(worker_alive),".to_string());
+          worker_impl_new_outro.push("};".to_string());
+          worker_impl_new_outro.push(format!("(driver, Self {{{worker_fields}}})"));
+          worker_impl_new_outro.push("}".to_string());
+        } else if !method_is_static {
+          if method_is_blocking {
+            worker_impl_new_match.push(format!("WorkerFuncs::{enum_name}({enum_arg_names}) => {{ {} send_ret.send(Box::new({object_name}.{method_name}({method_arg_names}))).expect(\"Failed to send return value of {enum_name} in Worker\") }},", tracing_span_string(&class_name, &method_name, &method_arg_names)));
+            worker_impl_new_match.push(format!("WorkerFuncs::{enum_name}Then(worker_then, {method_arg_names}) => {{ {} worker_then({object_name}.{method_name}({method_arg_names})) }},", tracing_span_string(&class_name, &method_name, &method_arg_names)));
+          } else {
+            worker_impl_new_match.push(format!("WorkerFuncs::{enum_name}({enum_arg_names}) => {{ {} {object_name}.{method_name}({method_arg_names}) }},", tracing_span_string(&class_name, &method_name, &method_arg_names)));
+          }
+        }
+
+        // Generate Serde Messages (streams can't be sent as a single response, so they're left out)
+        if args.serde && !method_is_static && !method_is_streaming {
+          let request_fields = params_to_named_fields_string(method);
+          if method_arg_names.split(", ").any(|x| x == "correlation_id") {
+            emit_error!(method.sig, "Method {}::{} has an argument named correlation_id, which is used by {}WorkerRequest. Please rename it.", class_name, method_name, class_name);
+          }
+          request_enum_output.push(format!("#[serde(rename = \"{method_name}\")]"));
+          if method_is_blocking {
+            let request_fields = if request_fields.is_empty() { "correlation_id: u64".to_string() } else { format!("correlation_id: u64, {request_fields}") };
+            request_enum_output.push(format!("{enum_name} {{ {request_fields} }},"));
+            response_enum_output.push(format!("#[serde(rename = \"{method_name}\")]"));
+            response_enum_output.push(format!("{enum_name} {{ correlation_id: u64, value: {method_return_type_str} }},"));
+            handle_request_output.push(format!("{class_name}WorkerRequest::{enum_name} {{ correlation_id, {method_arg_names} }} => Some({class_name}WorkerResponse::{enum_name} {{ correlation_id, value: self.{method_name}({method_arg_names}) }}),"));
+          } else {
+            request_enum_output.push(format!("{enum_name} {{ {request_fields} }},"));
+            handle_request_output.push(format!("{class_name}WorkerRequest::{enum_name} {{ {method_arg_names} }} => {{ self.{method_name}({method_arg_names}); None }},"));
+          }
+
+          // Generate Remote Worker
+          if args.remote {
+            remote_output.push(format!("pub {method_signature} {{"));
+            if method_is_blocking {
+              remote_output.push(format!("match self.client.call(|correlation_id| {class_name}WorkerRequest::{enum_name} {{ correlation_id, {method_arg_names} }}) {{"));
+              remote_output.push(format!("{class_name}WorkerResponse::{enum_name} {{ value, .. }} => value,"));
+              remote_output.push(format!("_ => panic!(\"Remote worker sent the wrong response to {method_name}\"),"));
+              remote_output.push("}".to_string());
+              correlated_output.push(format!("{class_name}WorkerResponse::{enum_name} {{ correlation_id, .. }} => correlation_id,"));
+            } else {
+              remote_output.push(format!("self.client.send(&{class_name}WorkerRequest::{enum_name} {{ {method_arg_names} }});"));
+            }
+            remote_output.push("}".to_string());
+          }
+        }
+
+        // Generate Recorder
+        if args.record && !method_is_static && method_is_streaming {
+          recorded_enum_output.push(format!("#[serde(rename = \"{method_name}\")]"));
+          recorded_enum_output.push(format!("{enum_name},"));
+          recorded_skipped.push(format!("{class_name}WorkerMessage::{enum_name}"));
+        } else if args.record && !method_is_static {
+          if method_is_blocking {
+            recorded_enum_output.push(format!("#[serde(rename = \"{method_name}_then\")]"));
+            recorded_enum_output.push(format!("{enum_name}Then,"));
+            recorded_skipped.push(format!("{class_name}WorkerMessage::{enum_name}Then"));
+          }
+          recorded_enum_output.push(format!("#[serde(rename = \"{method_name}\")]"));
+          recorded_enum_output.push(format!("{enum_name} {{ {} }},", params_to_named_fields_string(method)));
+          recorded_replay_output.push(format!("{class_name}WorkerMessage::{enum_name} {{ {method_arg_names} }} => {{ self.{method_name}({method_arg_names}); }},"));
+        }
+
+        // Generate Impl ThingyWorker (streaming methods return the Stream, and keep the Sink for the worker)
+        if let (Some(streaming_buffer), Some(streaming_item), false) = (streaming_buffer, &streaming_item, method_is_static) {
+          let mut stream_method = method.clone();
+          stream_method.sig.inputs.pop();
+          stream_method.sig.output = parse_quote!(-> nano_services::Stream<#streaming_item>);
+          let sink_name = method_arg_names.rsplit(", ").next().unwrap_or_default();
+
+          worker_impl_output.push(format!("pub {} {{", handle_signature_string(&stream_method)));
+          worker_impl_output.push(format!("let ({sink_name}, worker_stream) = nano_services::Sink::bounded({streaming_buffer});"));
+          worker_impl_output.push(record_message_string(args.record, &method_name, None));
+          worker_impl_output.push(format!("self.send.send(nano_services::Envelope::new(WorkerFuncs::{enum_name}({enum_arg_names}))).expect(\"Failed to send {enum_name} to Worker\");"));
+          worker_impl_output.push("worker_stream".to_string());
+          worker_impl_output.push("}".to_string());
+
+          // Generate Worker Pool and Sharded Worker
+          let stream_arg_names = method_arg_names.rsplit_once(", ").map(|(x, _)| x).unwrap_or_default();
+          push_forwarder(&mut pool_output, &mut shards_output, shard_key.as_deref(), &handle_signature_string(&stream_method), &format!("{method_name}({stream_arg_names})"));
+        } else if !method_is_static {
+          worker_impl_output.push(format!("pub {method_signature} {{"));
+          if method_is_blocking {
+            worker_impl_output.push(same_thread_check_string(args.consumers, &class_name, &method_name));
+            worker_impl_output.push(format!("let (send_ret, recv_ret) = futures::channel::oneshot::channel::<Box<{method_return_type_str}>>();;"));
+          }
+          worker_impl_output.push(record_message_string(args.record, &method_name, Some(&method_arg_names)));
+          worker_impl_output.push(event_message_string(method_is_logged, &method_name, &method_arg_names));
+          if method_is_logged {
+            event_enum_output.push(format!("#[serde(rename = \"{method_name}\")]"));
+            event_enum_output.push(format!("{enum_name} {{ {} }},", params_to_named_fields_string(method)));
+            event_apply_output.push(format!("{class_name}WorkerEvent::{enum_name} {{ {method_arg_names} }} => {{ {object_name}.{method_name}({method_arg_names}); }},"));
+          }
+          worker_impl_output.push(format!("self.send.send(nano_services::Envelope::new(WorkerFuncs::{enum_name}({enum_arg_names}))).expect(\"Failed to send {enum_name} to Worker\");"));
+          if method_is_blocking {
+            worker_impl_output.push(record_release.to_string());
+            if method_is_logged {
+              worker_impl_output.push("drop(worker_event);".to_string());
+            }
+            worker_impl_output.push(deadlock_wait_string(args.consumers, &class_name, &method_name));
+            worker_impl_output.push("match futures::executor::block_on(async move { recv_ret.await }) {".to_string());
+            worker_impl_output.push("Ok(x) => *x,".to_string());
+            worker_impl_output.push(format!("Err(_) => panic!(\"Error on async await of result in {method_name}\"),"));
+            worker_impl_output.push("}".to_string());
+          }
+          worker_impl_output.push("}".to_string());
+
+          // Generate Worker Pool and Sharded Worker (non-blocking methods can also be sent to every worker of the pool,
+          // with a copy of the args)
+          push_forwarder(&mut pool_output, &mut shards_output, shard_key.as_deref(), &method_signature, &format!("{method_name}({method_arg_names})"));
+          if !method_is_blocking {
+            let mut broadcast_method = method.clone();
+            broadcast_method.sig.ident = syn::Ident::new(&format!("broadcast_{method_name}"), method.sig.ident.span());
+            broadcast_method.sig.inputs.iter_mut().for_each(|arg| if let FnArg::Typed(arg) = arg {
+              let ty = &arg.ty;
+              *arg.ty = parse_quote!(impl Into<#ty> + Clone);
+            });
+            let broadcast_args = method_arg_names.split(", ").filter(|x| !x.is_empty()).map(|x| format!("{x}.clone().into()")).collect::<Vec<_>>().join(", ");
+            pool_output.push(format!("pub {} {{", handle_signature_string(&broadcast_method)));
+            pool_output.push("for worker in self.workers.iter() {".to_string());
+            pool_output.push(format!("worker.{method_name}({broadcast_args});"));
+            pool_output.push("}".to_string());
+            pool_output.push("}".to_string());
+          }
+        }
+
+        // Generate Impl ThingyWorker (blocking methods can also pass their result to a closure, instead of waiting)
+        if method_is_blocking && !method_is_static {
+          let mut then_method = method.clone();
+          let return_type: Type = parse_str(method_return_type_str).unwrap_or_else(|_| parse_quote!(()));
+          then_method.sig.ident = syn::Ident::new(&format!("{method_name}_then"), method.sig.ident.span());
+          then_method.sig.inputs.push(parse_quote!(worker_then: impl FnOnce(#return_type) + Send + 'static));
+          then_method.sig.output = ReturnType::Default;
+
+          worker_impl_output.push(format!("pub {} {{", handle_signature_string(&then_method)));
+          worker_impl_output.push(record_message_string(args.record, &format!("{method_name}_then"), None));
+          worker_impl_output.push(event_message_string(method_is_logged, &method_name, &method_arg_names));
+          worker_impl_output.push(format!("self.send.send(nano_services::Envelope::new(WorkerFuncs::{enum_name}Then(Box::new(worker_then), {method_arg_names}))).expect(\"Failed to send {enum_name}Then to Worker\");"));
+          worker_impl_output.push("}".to_string());
+
+          // The result can also be sent on to a method of another worker (or anything else that's Send)
+          let mut reply_method = method.clone();
+          reply_method.sig.ident = syn::Ident::new(&format!("{method_name}_reply_to"), method.sig.ident.span());
+          reply_method.sig.generics = parse_quote!(<ReplyTarget: Send + 'static>);
+          reply_method.sig.inputs.push(parse_quote!(reply_target: ReplyTarget));
+          reply_method.sig.inputs.push(parse_quote!(reply_method: fn(&ReplyTarget, #return_type)));
+          reply_method.sig.output = ReturnType::Default;
+
+          let then_arg_names = if method_arg_names.is_empty() { String::new() } else { format!("{method_arg_names}, ") };
+          worker_impl_output.push(format!("pub {} {{", handle_signature_string(&reply_method)));
+          worker_impl_output.push(format!("self.{method_name}_then({then_arg_names}move |value| reply_method(&reply_target, value));"));
+          worker_impl_output.push("}".to_string());
+
+          // Generate Worker Pool and Sharded Worker
+          push_forwarder(&mut pool_output, &mut shards_output, shard_key.as_deref(), &handle_signature_string(&then_method), &format!("{method_name}_then({then_arg_names}worker_then)"));
+          push_forwarder(&mut pool_output, &mut shards_output, shard_key.as_deref(), &handle_signature_string(&reply_method), &format!("{method_name}_reply_to({then_arg_names}reply_target, reply_method)"));
+        }
+      }
+    });
+
+    // Generate the worker loop (waits for the next message, delayed message, or periodic method)
+    let worker_handle_args = if METRICS_ENABLED {
+      format!("&mut {object_name}, &mut worker_timers, &worker_processed, &worker_metrics, message")
+    } else {
+      format!("&mut {object_name}, &mut worker_timers, &worker_processed, message")
+    };
+    // Upgrades don't go through worker_handle, so they time themselves and enter the sender's span here
+    let mut upgrade_intro = Vec::new();
+    if METRICS_ENABLED {
+      upgrade_intro.push("let worker_dequeued = std::time::Instant::now();".to_string());
+      upgrade_intro.push("let worker_queue_wait = worker_dequeued.saturating_duration_since(message.sent);".to_string());
+    }
+    if TRACING_ENABLED {
+      upgrade_intro.push("let _worker_parent_span = message.span.enter();".to_string());
+      upgrade_intro.push(tracing_span_string(&class_name, "upgrade", ""));
+    }
+    if !worker_impl_new_intro.is_empty() {
+      let periodic_times = periodic_methods.iter().fold(String::new(), |cur, (_, every_ms)| {
+        cur + &format!("std::time::Instant::now() + std::time::Duration::from_millis({every_ms}), ")
+      });
+      let worker_deadline = if periodic_methods.is_empty() {
+        "worker_timers.first().map(|(x, _)| *x)".to_string()
+      } else {
+        worker_impl_new_intro.push(format!("let mut worker_periodic_times = [{periodic_times}];"));
+        "worker_periodic_times.iter().chain(worker_timers.first().map(|(x, _)| x)).min().copied()".to_string()
+      };
+
+      if let Some(method_name) = hook_methods.get("on_start") {
+        worker_impl_new_intro.push(hook_call_string(&object_name, method_name, hook_methods.get("on_panic")));
+      }
+      if hook_methods.contains_key("on_idle") {
+        worker_impl_new_intro.push("let mut worker_busy = false;".to_string());
+      }
+
+      worker_impl_new_intro.push("loop {".to_string());
+      worker_impl_new_intro.push("let worker_now = std::time::Instant::now();".to_string());
+      periodic_methods.iter().enumerate().for_each(|(index, (method_name, every_ms))| {
+        worker_impl_new_intro.push(format!("if worker_periodic_times[{index}] <= worker_now {{"));
+        worker_impl_new_intro.push(format!("worker_periodic_times[{index}] = worker_now + std::time::Duration::from_millis({every_ms});"));
+        worker_impl_new_intro.push(hook_call_string(&object_name, method_name, hook_methods.get("on_panic")));
+        worker_impl_new_intro.push("}".to_string());
+      });
+      worker_impl_new_intro.push("let message = if worker_timers.first().is_some_and(|(x, _)| *x <= worker_now) {".to_string());
+      worker_impl_new_intro.push("worker_timers.remove(0).1.fired()".to_string());
+      worker_impl_new_intro.push("} else {".to_string());
+      if let Some(method_name) = hook_methods.get("on_idle") {
+        worker_impl_new_intro.push("if worker_busy && recv_func.is_empty() {".to_string());
+        worker_impl_new_intro.push("worker_busy = false;".to_string());
+        worker_impl_new_intro.push(hook_call_string(&object_name, method_name, hook_methods.get("on_panic")));
+        worker_impl_new_intro.push("}".to_string());
+      }
+      worker_impl_new_intro.push(format!("match {worker_deadline} {{"));
+      worker_impl_new_intro.push("None => recv_func.recv().expect(\"Error in Worker when receiving message \"),".to_string());
+      worker_impl_new_intro.push("Some(deadline) => match recv_func.recv_deadline(deadline) {".to_string());
+      worker_impl_new_intro.push("Ok(message) => message,".to_string());
+      worker_impl_new_intro.push("Err(crossbeam_channel::RecvTimeoutError::Timeout) => continue,".to_string());
+      worker_impl_new_intro.push("Err(crossbeam_channel::RecvTimeoutError::Disconnected) => panic!(\"Error in Worker when receiving message \"),".to_string());
+      worker_impl_new_intro.push("},".to_string());
+      worker_impl_new_intro.push("}".to_string());
+      worker_impl_new_intro.push("};".to_string());
+      if hook_methods.contains_key("on_idle") {
+        worker_impl_new_intro.push("worker_busy = true;".to_string());
+      }
+      // The loop owns the state, so it can pass it by value to an upgrade and keep the new one
+      if upgradable {
+        worker_impl_new_intro.push("if matches!(*message.func, WorkerFuncs::WorkerUpgrade(_)) {".to_string());
+        worker_impl_new_intro.extend(upgrade_intro.iter().cloned());
+        worker_impl_new_intro.push("let WorkerFuncs::WorkerUpgrade(func) = *message.func else { unreachable!() };".to_string());
+        worker_impl_new_intro.push(format!("{object_name} = func({object_name});"));
+        if METRICS_ENABLED {
+          worker_impl_new_intro.push("worker_metrics.record(\"upgrade\", worker_queue_wait, worker_dequeued.elapsed(), false);".to_string());
+        }
+        worker_impl_new_intro.push("worker_processed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);".to_string());
+        worker_impl_new_intro.push("continue;".to_string());
+        worker_impl_new_intro.push("}".to_string());
+      }
+      worker_impl_new_intro.push(format!("if !Self::worker_handle({worker_handle_args}) {{"));
+      worker_impl_new_intro.push("break;".to_string());
+      worker_impl_new_intro.push("}".to_string());
+      worker_impl_new_intro.push("}".to_string());
+      if let Some(method_name) = hook_methods.get("on_stop") {
+        worker_impl_new_intro.push(format!("{object_name}.{method_name}();"));
+      }
+    }
+
+    // Generate the handling of a single message (shared by the worker loop and the manual driver)
+    let worker_handle_params = if METRICS_ENABLED {
+      format!("{object_name}: &mut {class_name}, worker_timers: &mut Vec<(std::time::Instant, nano_services::Envelope<WorkerFuncs>)>, worker_processed: &std::sync::atomic::AtomicU64, worker_metrics: &nano_services::metrics::MetricsRecorder, message: nano_services::Envelope<WorkerFuncs>")
+    } else {
+      format!("{object_name}: &mut {class_name}, worker_timers: &mut Vec<(std::time::Instant, nano_services::Envelope<WorkerFuncs>)>, worker_processed: &std::sync::atomic::AtomicU64, message: nano_services::Envelope<WorkerFuncs>")
+    };
+    let mut worker_handle_output = vec![format!("fn worker_handle({worker_handle_params}) -> bool {{")];
+    // Scheduling a delayed message isn't counted in messages_processed or the metrics (the delayed message is, as an
+    // exec, once its timer fires)
+    worker_handle_output.extend([
+      "if matches!(*message.func, WorkerFuncs::WorkerSendAt(..)) {".to_string(),
+      "let WorkerFuncs::WorkerSendAt(time, func) = *message.func else { unreachable!() };".to_string(),
+      "worker_timers.insert(worker_timers.partition_point(|(x, _)| *x <= time), (time, func));".to_string(),
+      "return true;".to_string(),
+      "}".to_string()]);
+    if METRICS_ENABLED {
+      worker_handle_output.push("let worker_dequeued = std::time::Instant::now();".to_string());
+      worker_handle_output.push("let worker_method_name = message.func.method_name();".to_string());
+      worker_handle_output.push("let worker_queue_wait = worker_dequeued.saturating_duration_since(message.sent);".to_string());
+    }
+    if TRACING_ENABLED {
+      worker_handle_output.push("let _worker_parent_span = message.span.enter();".to_string());
+    }
+    if METRICS_ENABLED || hook_methods.contains_key("on_panic") {
+      worker_handle_output.push(format!("let worker_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| Self::worker_dispatch({object_name}, *message.func)));"));
+      if METRICS_ENABLED {
+        worker_handle_output.push("worker_metrics.record(worker_method_name, worker_queue_wait, worker_dequeued.elapsed(), worker_result.is_err());".to_string());
+      }
+      worker_handle_output.push("match worker_result {".to_string());
+      worker_handle_output.push("Ok(true) => {},".to_string());
+      worker_handle_output.push("Ok(false) => return false,".to_string());
+      worker_handle_output.push("Err(payload) => {".to_string());
+      if let Some(method_name) = hook_methods.get("on_panic") {
+        worker_handle_output.push(format!("{object_name}.{method_name}(&*payload);"));
+      }
+      worker_handle_output.push("std::panic::resume_unwind(payload);".to_string());
+      worker_handle_output.push("}".to_string());
+      worker_handle_output.push("}".to_string());
+    } else {
+      worker_handle_output.push(format!("if !Self::worker_dispatch({object_name}, *message.func) {{"));
+      worker_handle_output.push("return false;".to_string());
+      worker_handle_output.push("}".to_string());
+    }
+    worker_handle_output.push("worker_processed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);".to_string());
+    worker_handle_output.push("true".to_string());
+    worker_handle_output.push("}".to_string());
+
+    // Generate Recorder (replay sends the recorded messages again, and counts the ones it can't send because they were
+    // closures or streams)
+    if args.record {
+      recorded_enum_output.push("}".to_string());
+      worker_impl_output.extend([
+        "pub fn replay(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<nano_services::record::Replay> {".to_string(),
+        "let mut replay = nano_services::record::Replay::default();".to_string(),
+        format!("for entry in nano_services::record::read::<{class_name}WorkerMessage>(path)? {{"),
+        "match entry.message {".to_string()]);
+      worker_impl_output.append(&mut recorded_replay_output);
+      worker_impl_output.extend([
+        format!("{class_name}WorkerMessage::StopThread => continue,"),
+        format!("{} => {{ replay.skipped += 1; continue; }},", recorded_skipped.join(" | ")),
+        "}".to_string(),
+        "replay.replayed += 1;".to_string(),
+        "}".to_string(),
+        "Ok(replay)".to_string(),
+        "}".to_string()]);
+    }
+
+    // Generate Event Log (open() rebuilds the state from the log before spawning the worker, and compact() replaces the
+    // events that the worker has handled with a snapshot)
+    if args.event_log && !worker_impl_new_intro.is_empty() {
+      event_enum_output.push("}".to_string());
+      let open_params = if constructor_params.is_empty() { String::new() } else { format!(", {constructor_params}") };
+      worker_impl_output.extend([
+        format!("pub fn open(event_log_path: impl AsRef<std::path::Path>{open_params}) -> std::io::Result<(std::thread::JoinHandle<()>, Self)> {{"),
+        format!("let worker_events = nano_services::event_log::read::<{class_name}WorkerEvent>(&event_log_path)?;"),
+        "let worker_event_log = nano_services::event_log::EventLog::open(&event_log_path)?;".to_string(),
+        format!("let mut {object_name} = {class_name}::new({constructor_arg_names});"),
+        "for event in worker_events {".to_string(),
+        "match event {".to_string()]);
+      worker_impl_output.append(&mut event_apply_output);
+      worker_impl_output.extend([
+        "}".to_string(),
+        "}".to_string(),
+        format!("let (handle, mut worker) = Self::worker_spawn(move || {object_name});"),
+        "worker.event_log = std::sync::Arc::new(worker_event_log);".to_string(),
+        "Ok((handle, worker))".to_string(),
+        "}".to_string(),
+        "pub fn compact(&self) -> std::io::Result<()> {".to_string(),
+        same_thread_check_string(args.consumers, &class_name, "compact"),
+        "let (send_ret, recv_ret) = futures::channel::oneshot::channel::<std::io::Result<()>>();".to_string(),
+        "let event_log = std::sync::Arc::clone(&self.event_log);".to_string(),
+        // The recorder is locked before the event log, like in the logged methods, so they can't deadlock
+        record_message_string(args.record, "compact", None),
+        "let worker_event = self.event_log.lock();".to_string(),
+        "let offset = worker_event.offset()?;".to_string(),
+        format!("let func = move |{object_name}: &mut {class_name}| if send_ret.send(event_log.compact(offset, {object_name})).is_err() {{ panic!(\"Failed to send return value of compact in Worker\") }};"),
+        "self.send.send(nano_services::Envelope::new(WorkerFuncs::WorkerExec(Box::new(func)))).expect(\"Failed to send compact to Worker\");".to_string(),
+        record_release.to_string(),
+        "drop(worker_event);".to_string(),
+        deadlock_wait_string(args.consumers, &class_name, "compact"),
+        "match futures::executor::block_on(async move { recv_ret.await }) {".to_string(),
+        "Ok(x) => x,".to_string(),
+        "Err(_) => panic!(\"Error on async await of result in compact\"),".to_string(),
+        "}".to_string(),
+        "}".to_string()]);
+    } else {
+      event_enum_output.clear();
+    }
+
+    // Generate Serde Messages
+    if args.serde {
+      request_enum_output.push("}".to_string());
+      response_enum_output.push("}".to_string());
+      handle_request_output.push("}".to_string());
+      handle_request_output.push("}".to_string());
+      worker_impl_output.append(&mut handle_request_output);
+    }
+
+    // Generate Remote Worker
+    if args.remote {
+      remote_output.push("}".to_string());
+      correlated_output.extend(["}".to_string(), "}".to_string(), "}".to_string()]);
+    }
+
+    // Generate Manual Driver
+    let driver_handle_args = if METRICS_ENABLED {
+      "self.state.as_mut().expect(Self::WORKER_STATE_LOST), &mut self.timers, &self.processed, &self.metrics, message"
+    } else {
+      "self.state.as_mut().expect(Self::WORKER_STATE_LOST), &mut self.timers, &self.processed, message"
+    };
+    let mut worker_driver_output = vec![
+      format!("pub(crate) struct {class_name}WorkerDriver {{"),
+      // None once an upgrade has panicked (and taken the old state with it)
+      format!("state: Option<{class_name}>,"),
+      "recv: crossbeam_channel::Receiver<nano_services::Envelope<WorkerFuncs>>,".to_string(),
+      "timers: Vec<(std::time::Instant, nano_services::Envelope<WorkerFuncs>)>,".to_string(),
+      "processed: std::sync::Arc<std::sync::atomic::AtomicU64>,".to_string()];
+    if METRICS_ENABLED {
+      worker_driver_output.push("metrics: std::sync::Arc<nano_services::metrics::MetricsRecorder>,".to_string());
+    }
+    worker_driver_output.extend([
+      "alive: Option<std::sync::Arc<()>>,".to_string(),
+      "}".to_string(),
+      format!("impl {class_name}WorkerDriver {{"),
+      "const WORKER_STATE_LOST: &'static str = \"The worker's state was lost when an upgrade panicked\";".to_string(),
+      "pub fn pending(&self) -> usize {".to_string(),
+      "let now = std::time::Instant::now();".to_string(),
+      "self.recv.len() + self.timers.iter().filter(|(x, _)| *x <= now).count()".to_string(),
+      "}".to_string(),
+      "pub fn step(&mut self) -> bool {".to_string(),
+      "if self.alive.is_none() {".to_string(),
+      "return false;".to_string(),
+      "}".to_string(),
+      "let message = if self.timers.first().is_some_and(|(x, _)| *x <= std::time::Instant::now()) {".to_string(),
+      "self.timers.remove(0).1.fired()".to_string(),
+      "} else {".to_string(),
+      "match self.recv.try_recv() {".to_string(),
+      "Ok(message) => message,".to_string(),
+      "Err(_) => return false,".to_string(),
+      "}".to_string(),
+      "};".to_string()]);
+    if upgradable {
+      worker_driver_output.extend([
+        "if matches!(*message.func, WorkerFuncs::WorkerUpgrade(_)) {".to_string()]);
+      worker_driver_output.extend(upgrade_intro.iter().cloned());
+      worker_driver_output.extend([
+        "let WorkerFuncs::WorkerUpgrade(func) = *message.func else { unreachable!() };".to_string(),
+        "let state = self.state.take().expect(Self::WORKER_STATE_LOST);".to_string(),
+        "self.state = Some(func(state));".to_string()]);
+      if METRICS_ENABLED {
+        worker_driver_output.push("self.metrics.record(\"upgrade\", worker_queue_wait, worker_dequeued.elapsed(), false);".to_string());
+      }
+      worker_driver_output.extend([
+        "self.processed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);".to_string(),
+        "return true;".to_string(),
+        "}".to_string()]);
+    }
+    worker_driver_output.extend([
+      format!("if !{class_name}Worker::worker_handle({driver_handle_args}) {{"),
+      "self.alive = None;".to_string(),
+      "}".to_string(),
+      "true".to_string(),
+      "}".to_string(),
+      "pub fn step_all(&mut self) -> usize {".to_string(),
+      "let mut steps = 0;".to_string(),
+      "while self.step() {".to_string(),
+      "steps += 1;".to_string(),
+      "}".to_string(),
+      "steps".to_string(),
+      "}".to_string(),
+      format!("pub fn state(&self) -> &{class_name} {{"),
+      "self.state.as_ref().expect(Self::WORKER_STATE_LOST)".to_string(),
+      "}".to_string(),
+      format!("pub fn state_mut(&mut self) -> &mut {class_name} {{"),
+      "self.state.as_mut().expect(Self::WORKER_STATE_LOST)".to_string(),
+      "}".to_string(),
+      "}".to_string()]);
+    if worker_impl_new_intro.is_empty() {
+      worker_driver_output.clear();
+    }
+
+    // Generate Worker Pool
+    let mut worker_pool_output = vec![
+      "#[derive(Clone, Debug)]".to_string(),
+      format!("pub(crate) struct {class_name}WorkerPool {{"),
+      format!("workers: std::sync::Arc<Vec<{class_name}Worker>>,"),
+      "next: std::sync::Arc<std::sync::atomic::AtomicUsize>,".to_string(),
+      "routing: nano_services::Routing,".to_string(),
+      "}".to_string(),
+      "#[allow(dead_code)]".to_string(),
+      format!("impl {class_name}WorkerPool {{")];
+    worker_pool_output.append(&mut pool_new_output);
+    worker_pool_output.extend([
+      "pub fn with_routing(mut self, routing: nano_services::Routing) -> Self {".to_string(),
+      "self.routing = routing;".to_string(),
+      "self".to_string(),
+      "}".to_string(),
+      format!("pub fn workers(&self) -> &[{class_name}Worker] {{"),
+      "&self.workers".to_string(),
+      "}".to_string(),
+      "pub fn pool_size(&self) -> usize {".to_string(),
+      "self.workers.len()".to_string(),
+      "}".to_string(),
+      "pub fn stop_thread(&self) {".to_string(),
+      "for worker in self.workers.iter() {".to_string(),
+      "worker.stop_thread();".to_string(),
+      "}".to_string(),
+      "}".to_string(),
+      format!("fn pool_worker(&self) -> &{class_name}Worker {{"),
+      "&self.workers[nano_services::pool::pick(self.routing, &self.next, self.workers.len(), |x| self.workers[x].queue_len())]".to_string(),
+      "}".to_string()]);
+    worker_pool_output.append(&mut pool_output);
+    worker_pool_output.push("}".to_string());
+
+    // Generate Sharded Worker (each call goes to the worker for its #[shard_key] argument, so calls for the same key
+    // are handled in order)
+    worker_pool_output.extend([
+      "#[derive(Clone, Debug)]".to_string(),
+      format!("pub(crate) struct {class_name}WorkerShards {{"),
+      format!("workers: std::sync::Arc<Vec<{class_name}Worker>>,"),
+      "}".to_string(),
+      "#[allow(dead_code)]".to_string(),
+      format!("impl {class_name}WorkerShards {{"),
+      format!("pub fn new(count: usize, args: impl FnMut(usize) -> {pool_args_type}) -> (Vec<std::thread::JoinHandle<()>>, Self) {{"),
+      format!("let (handles, pool) = {class_name}WorkerPool::new(count, args);"),
+      "(handles, Self { workers: pool.workers })".to_string(),
+      "}".to_string(),
+      format!("pub fn workers(&self) -> &[{class_name}Worker] {{"),
+      "&self.workers".to_string(),
+      "}".to_string(),
+      "pub fn shard_count(&self) -> usize {".to_string(),
+      "self.workers.len()".to_string(),
+      "}".to_string(),
+      format!("pub fn shard<K: std::hash::Hash + ?Sized>(&self, key: &K) -> &{class_name}Worker {{"),
+      "&self.workers[nano_services::pool::shard_index(key, self.workers.len())]".to_string(),
+      "}".to_string(),
+      "pub fn stop_thread(&self) {".to_string(),
+      "for worker in self.workers.iter() {".to_string(),
+      "worker.stop_thread();".to_string(),
+      "}".to_string(),
+      "}".to_string()]);
+    worker_pool_output.append(&mut shards_output);
+    worker_pool_output.push("}".to_string());
+    if worker_impl_new_intro.is_empty() {
+      worker_pool_output.clear();
+    }
+
+    // Generate WorkerFuncs Enum
+    funcs_enum_output.push("}".to_string());
+
+    // Generate Metrics
+    funcs_names_output.push("}".to_string());
+    funcs_names_output.push("}".to_string());
+    funcs_names_output.push("}".to_string());
+    if !METRICS_ENABLED {
+      funcs_names_output.clear();
+    }
+
+    // Generate Impl Worker
+    worker_impl_output.push(worker_impl_new_intro.join("\n"));
+    worker_impl_output.push(worker_impl_new_outro.join("\n"));
+    worker_impl_output.push(worker_handle_output.join("\n"));
+    worker_impl_output.push(format!("fn worker_dispatch({object_name}: &mut {class_name}, func: WorkerFuncs) -> bool {{"));
+    if TRACING_ENABLED {
+      worker_impl_output.push("#[allow(unused_imports)]".to_string());
+      worker_impl_output.push("use nano_services::{TraceArgDebug, TraceArgOpaque};".to_string());
+    }
+    worker_impl_output.push("match func {".to_string());
+    worker_impl_output.push(worker_impl_new_match.join("\n"));
+    worker_impl_output.push("}".to_string());
+    worker_impl_output.push("true".to_string());
+    worker_impl_output.push("}".to_string());
+    worker_impl_output.push("}".to_string());
+
+    let output = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n",
+        input.to_token_stream(),
+        includes_output.join("\n"),
+        funcs_enum_output.join("\n"),
+        funcs_names_output.join("\n"),
+        worker_struct_output.join("\n"),
+        worker_impl_output.join("\n"),
+        worker_driver_output.join("\n"),
+        worker_pool_output.join("\n"),
+        recorded_enum_output.join("\n"),
+        event_enum_output.join("\n"),
+        request_enum_output.join("\n"),
+        response_enum_output.join("\n"),
+        remote_output.join("\n"),
+        correlated_output.join("\n")
+    );
+
+    // Write the expansion report (only when asked for, so normal builds stay quiet)
+    let expand_dir = std::env::var_os("NANO_SERVICES_EXPAND").map(PathBuf::from);
+    if let Some(expand_dir) = expand_dir.or_else(|| args.debug.then(default_expand_dir)) {
+        // Same-named classes in different modules get their own report, named after where #[worker] is
+        let call_site = Span::call_site();
+        let call_site_file = PathBuf::from(call_site.file()).with_extension("").to_string_lossy().replace(|x: char| !x.is_ascii_alphanumeric(), "_");
+        let report_path = expand_dir.join(format!("{class_name}Worker_{call_site_file}_{}.rs", call_site.line()));
+        let report = format!(
+            "// Expansion report for {class_name}Worker\n//\n// {:<24} {:<9} {:<7} variant\n{}\n\n{output}",
+            "method",
+            "blocking",
+            "static",
+            method_summaries.join("\n"));
+        if let Err(err) = std::fs::create_dir_all(&expand_dir).and_then(|_| std::fs::write(&report_path, report)) {
+            emit_call_site_error!("Failed to write the expansion report to {}: {err}", report_path.display());
+        }
+    }
+
+    output.parse().expect("Generated invalid tokens")
+}
+
+// Arguments of #[worker(...)]
+struct WorkerArgs {
+    debug: bool,
+    record: bool,
+    // Number of threads that take messages from the worker's mailbox
+    consumers: usize,
+    snapshot: bool,
+    event_log: bool,
+    // Also set by remote, which sends the request and response enums
+    serde: bool,
+    remote: bool,
+}
+
+fn parse_worker_args(args: &AttributeArgs) -> WorkerArgs {
+    let mut worker_args = WorkerArgs { debug: false, record: false, consumers: 1, snapshot: false, event_log: false, serde: false, remote: false };
+    args.iter().for_each(|arg| match arg {
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("debug") => worker_args.debug = true,
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("record") => worker_args.record = true,
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("snapshot") => worker_args.snapshot = true,
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("event_log") => worker_args.event_log = true,
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("serde") => worker_args.serde = true,
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("remote") => {
+            worker_args.serde = true;
+            worker_args.remote = true;
+        }
+        NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Int(consumers), .. })) if path.is_ident("consumers") => {
+            match consumers.base10_parse::<usize>() {
+                Ok(consumers) if consumers > 0 => worker_args.consumers = consumers,
+                _ => emit_error!(consumers, "#[worker(consumers = N)] needs at least one consumer."),
+            }
+        }
+        _ => emit_error!(arg, "Unknown worker argument. The supported arguments are: debug, record, snapshot, event_log, serde, remote, consumers = N"),
+    });
+    worker_args
+}
+
+// <target dir>/nano_services of the crate being compiled
+fn default_expand_dir() -> PathBuf {
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from).unwrap_or_else(|| {
+        let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+        PathBuf::from(manifest_dir).join("target")
+    });
+    target_dir.join("nano_services")
+}
+
+#[proc_macro_attribute]
+pub fn blocking_method(_args: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+#[proc_macro_attribute]
+pub fn streaming(_args: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+#[proc_macro_attribute]
+pub fn periodic(_args: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+#[proc_macro_attribute]
+pub fn on_start(_args: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+#[proc_macro_attribute]
+pub fn on_stop(_args: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+#[proc_macro_attribute]
+pub fn on_idle(_args: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+#[proc_macro_attribute]
+pub fn on_panic(_args: TokenStream, input: TokenStream) -> TokenStream {
+    input
+}
+
+#[proc_macro_attribute]
+pub fn intro(_args: TokenStream, input: TokenStream) -> TokenStream {
+    let _input = input.clone();
+    let input = parse_macro_input!(input as ItemStruct);
+
+    //println!("----------------------------");
+    //println!("FIELDS:");
+    //input
+    //.fields
+    //.iter()
+    //.for_each(|field| { println!("{}", field.ident.as_ref().unwrap()); });
+    //println!("----------------------------");
+
+    let class_name = &input.ident;
+
+    let output = format!(
+        r#"
+      {_input}
+      impl {class_name} {{
+        pub fn introspect(){{
+          println!("Introspect");
+        }}
+      }}
+    "#
+    );
+
+    output.parse().expect("Generated invalid tokens")
+}
+
+// ------------------------------------
+// UNIT TESTS
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn basic_unit_test() {
+        assert_eq!(1, 1);
+    }
+}
+
+// ------------------------------------
+// HELPFUL STUFF
+
+// print type of variable
+//fn print_type_of<T>(_: &T) -> String {
+//  format!("{}", std::any::type_name::<T>())
+//}
+
+// List of options in syn::Type enum
+//FnArg::Typed(ty) => match &*ty.ty {
+//  Type::Array(_) => {format!("1")},
+//  Type::BareFn(_) => {format!("2")},
+//  Type::Group(_) => {format!("3")},
+//  Type::ImplTrait(_) => {format!("4")},
+//  Type::Infer(_) => {format!("5")},
+//  Type::Macro(_) => {format!("6")},
+//  Type::Never(_) => {format!("7")},
+//  Type::Paren(_) => {format!("8")},
+//  Type::Path(_) => {format!("9")},
+//  Type::Ptr(_) => {format!("10")},
+//  Type::Reference(_) => {format!("11")},
+//  Type::Slice(_) => {format!("12")},
+//  Type::TraitObject(_) => {format!("13")},
+//  Type::Tuple(_) => {format!("14")},
+//  Type::Verbatim(_) => {format!("15")},
+//}
+
+// macro on functions
+// https://stackoverflow.com/questions/52585719/how-do-i-create-a-proc-macro-attribute
+
+// For a tutorial on how to implement a proc_macro_atrribute!!!
+// https://doc.rust-lang.org/reference/procedural-macros.html
+// https://blog.logrocket.com/macros-in-rust-a-tutorial-with-examples/#proceduralmacrosinrust
+// https://blog.logrocket.com/macros-in-rust-a-tutorial-with-examples/#customderivemacros
+
+// Rename a function with a macro
+// https://github.com/LevitatingLion/rename-item/blob/main/src/lib.rs
+// https://github.com/Manishearth/rust-adorn/blob/master/src/lib.rs
+// https://dev.to/naufraghi/procedural-macro-in-rust-101-k3f
+// https://crates.io/crates/syn
+//#[proc_macro_attribute]
+//pub fn rename(attr: TokenStream, item: TokenStream) -> TokenStream {
+//    // Parse attribute and item
+//    let args = parse_macro_input!(attr as AttributeArgs);
+//    let mut item = parse_macro_input!(item as Item);
+//
+//    // Convert macro input to target name
+//    let name = MacroInput::from_list(&args).and_then(|input| input.into_name(Some(&item)));
+//
+//    // Apply target name to the item
+//    let toks = name.and_then(|name| {
+//        let ident = Ident::new(&name, Span::call_site());
+//        set_ident(&mut item, ident)?;
+//        Ok(item.into_token_stream())
+//    });
+//
+//    // Handle errors
+//    match toks {
+//        Ok(toks) => toks,
+//        Err(err) => err.write_errors(),
+//    }
+//    .into()
+//}
+
+// Capture stdio as macro for assert
+// https://users.rust-lang.org/t/how-to-test-functions-that-use-println/67188/5
@@ -0,0 +1,56 @@
+// Wait-for graph of the threads that are blocked on a worker (the "deadlock_detection" feature).
+// Before a blocking call waits for its result, the generated code checks whether the worker it's waiting on
+// is (through a chain of other blocking calls) waiting on the calling thread. If it is, nothing in the chain
+// can ever make progress, so the call panics with the whole chain instead of hanging.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread::ThreadId;
+
+struct Wait {
+    target: ThreadId,
+    call: &'static str,
+}
+
+static WAITS: Mutex<Option<HashMap<ThreadId, Wait>>> = Mutex::new(None);
+
+// Removes the calling thread from the graph once its blocking call has returned
+#[doc(hidden)]
+pub struct WaitGuard {
+    thread: ThreadId,
+}
+
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        let mut waits = WAITS.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(waits) = waits.as_mut() {
+            waits.remove(&self.thread);
+        }
+    }
+}
+
+fn describe(thread: ThreadId, call: &str, target: ThreadId) -> String {
+    format!("{thread:?} is waiting on {call} (in {target:?})")
+}
+
+#[doc(hidden)]
+pub fn wait_for(target: ThreadId, call: &'static str) -> WaitGuard {
+    let current = std::thread::current().id();
+    let mut guard = WAITS.lock().unwrap_or_else(|err| err.into_inner());
+    let waits = guard.get_or_insert_with(HashMap::new);
+
+    let mut chain = vec![describe(current, call, target)];
+    let mut next = target;
+    while next != current {
+        let Some(wait) = waits.get(&next) else {
+            waits.insert(current, Wait { target, call });
+            return WaitGuard { thread: current };
+        };
+        chain.push(describe(next, wait.call, wait.target));
+        next = wait.target;
+    }
+
+    let chain = chain.join("\n  ");
+    drop(guard);
+    panic!("Deadlock detected, this blocking call would never return:\n  {chain}");
+}
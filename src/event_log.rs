@@ -0,0 +1,166 @@
+// Durable mutation logs for #[worker(event_log)] services.
+// Each line of a log is one JSON event: a call of a non-blocking &mut self method (written by the thread that sent
+// it, before it reaches the mailbox), or a snapshot of the state that replaces every event before it.
+// Each event is synced to disk before its message is sent, so a call that a worker has handled is never lost, even if
+// the OS crashes. A crash while an event is written leaves its line incomplete, and that line is dropped.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+struct Log {
+    path: PathBuf,
+    file: File,
+}
+
+// Shared by every clone of a worker handle, so events are logged in the order they reach the mailbox
+#[derive(Default)]
+pub struct EventLog {
+    log: Mutex<Option<Log>>,
+}
+
+impl std::fmt::Debug for EventLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let log = self.log.lock().expect("Worker event log lock was poisoned");
+        f.debug_struct("EventLog").field("path", &log.as_ref().map(|x| &x.path)).finish()
+    }
+}
+
+#[derive(Serialize)]
+struct Snapshot<'a, T> {
+    snapshot: &'a T,
+}
+
+impl EventLog {
+    // Appends to the log at path (creating it if it doesn't exist), after dropping an incomplete last line
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(&path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let complete_len = contents.iter().rposition(|x| *x == b'\n').map_or(0, |x| x + 1);
+        if complete_len < contents.len() {
+            file.set_len(complete_len as u64)?;
+            file.sync_data()?;
+        }
+        Ok(EventLog { log: Mutex::new(Some(Log { path, file })) })
+    }
+
+    // Held by the generated code while it sends the message that it logged
+    #[doc(hidden)]
+    pub fn lock(&self) -> EventLogGuard<'_> {
+        EventLogGuard(self.log.lock().expect("Worker event log lock was poisoned"))
+    }
+
+    // Replaces the events before offset with a snapshot of the state they built (run by the worker, once it has
+    // handled every message that was logged before offset)
+    #[doc(hidden)]
+    pub fn compact<T: Serialize>(&self, offset: u64, state: &T) -> std::io::Result<()> {
+        let mut log = self.lock();
+        let Some(log) = log.0.as_mut() else {
+            return Ok(());
+        };
+
+        let mut later_events = Vec::new();
+        let mut reader = File::open(&log.path)?;
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_to_end(&mut later_events)?;
+
+        let mut compacted_path = log.path.clone().into_os_string();
+        compacted_path.push(".compacting");
+        let mut compacted = File::create(&compacted_path)?;
+        serde_json::to_writer(&mut compacted, &Snapshot { snapshot: state })?;
+        compacted.write_all(b"\n")?;
+        compacted.write_all(&later_events)?;
+        compacted.sync_all()?;
+        std::fs::rename(&compacted_path, &log.path)?;
+        log.file = OpenOptions::new().append(true).open(&log.path)?;
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+pub struct EventLogGuard<'a>(MutexGuard<'a, Option<Log>>);
+
+impl EventLogGuard<'_> {
+    // Written and synced before the message is sent, so it survives the process or the OS crashing
+    pub fn append(&mut self, event: impl FnOnce() -> serde_json::Value) {
+        let Some(log) = self.0.as_mut() else {
+            return;
+        };
+
+        let mut line = serde_json::to_vec(&event()).expect("Failed to serialize worker event");
+        line.push(b'\n');
+        log.file.write_all(&line).expect("Failed to write worker event");
+        log.file.sync_data().expect("Failed to sync worker event");
+    }
+
+    // Where the next event will be written (0 if there's no log)
+    pub fn offset(&self) -> std::io::Result<u64> {
+        match self.0.as_ref() {
+            Some(log) => Ok(log.file.metadata()?.len()),
+            None => Ok(0),
+        }
+    }
+}
+
+// Returns no events if there's no log at path yet, and ignores an incomplete last line
+pub fn read<E: DeserializeOwned>(path: impl AsRef<Path>) -> std::io::Result<Vec<E>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut reader = BufReader::new(file);
+    let mut events = Vec::new();
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        if line.pop() != Some(b'\n') {
+            break;
+        }
+        if !line.is_empty() {
+            events.push(serde_json::from_slice(&line)?);
+        }
+        line.clear();
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_common::TempPath;
+
+    #[test]
+    fn append_and_compact() {
+        let path = TempPath::new("event_log.jsonl");
+        EventLog::default().lock().append(|| serde_json::json!("skipped"));
+
+        let log = EventLog::open(&path).unwrap();
+        log.lock().append(|| serde_json::json!({"add": {"i": 1}}));
+        let offset = log.lock().offset().unwrap();
+        log.lock().append(|| serde_json::json!({"add": {"i": 2}}));
+        log.compact(offset, &1).unwrap();
+        log.lock().append(|| serde_json::json!({"add": {"i": 3}}));
+
+        let events = read::<serde_json::Value>(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(events, [serde_json::json!({"snapshot": 1}), serde_json::json!({"add": {"i": 2}}), serde_json::json!({"add": {"i": 3}})]);
+        assert!(read::<serde_json::Value>(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn incomplete_last_line() {
+        let path = TempPath::new("event_log_incomplete.jsonl");
+        std::fs::write(&path, "{\"add\": {\"i\": 1}}\n{\"add\": {\"i\"").unwrap();
+        assert_eq!(read::<serde_json::Value>(&path).unwrap(), [serde_json::json!({"add": {"i": 1}})]);
+
+        let log = EventLog::open(&path).unwrap();
+        log.lock().append(|| serde_json::json!({"add": {"i": 2}}));
+        let events = read::<serde_json::Value>(&path).unwrap();
+        assert_eq!(events, [serde_json::json!({"add": {"i": 1}}), serde_json::json!({"add": {"i": 2}})]);
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const HISTOGRAM_BUCKETS: usize = 32;
+
+// Durations bucketed by powers of two microseconds.
+// Bucket 0 holds everything under 1us, bucket n holds [2^(n-1)us, 2^n us), and the last bucket holds the rest.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; HISTOGRAM_BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    pub fn record(&mut self, duration: Duration) {
+        let micros = duration.as_micros();
+        let bucket = (u128::BITS - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.total.as_nanos() / u128::from(count)) as u64),
+        }
+    }
+
+    // Upper bound of the bucket that holds the given percentile (0.0 to 1.0), capped by the max
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let target = (self.count as f64 * percentile.clamp(0.0, 1.0)).ceil() as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target.max(1) {
+                return Duration::from_micros(1 << bucket).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MethodMetrics {
+    pub calls: u64,
+    pub panics: u64,
+    pub queue_wait: Histogram,
+    pub handler_time: Histogram,
+}
+
+// Snapshot of the metrics of every method a worker has handled, keyed by method name
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorkerMetrics {
+    pub methods: BTreeMap<&'static str, MethodMetrics>,
+}
+
+impl WorkerMetrics {
+    pub fn method(&self, method_name: &str) -> Option<&MethodMetrics> {
+        self.methods.get(method_name)
+    }
+}
+
+// Shared between a worker (which records) and its handles (which take snapshots)
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct MetricsRecorder {
+    metrics: Mutex<WorkerMetrics>,
+}
+
+impl MetricsRecorder {
+    pub fn record(&self, method_name: &'static str, queue_wait: Duration, handler_time: Duration, panicked: bool) {
+        let mut metrics = self.metrics.lock().expect("Worker metrics lock was poisoned");
+        let method = metrics.methods.entry(method_name).or_default();
+        method.calls += 1;
+        method.panics += u64::from(panicked);
+        method.queue_wait.record(queue_wait);
+        method.handler_time.record(handler_time);
+    }
+
+    pub fn snapshot(&self) -> WorkerMetrics {
+        self.metrics.lock().expect("Worker metrics lock was poisoned").clone()
+    }
+}
+
+// ------------------------------------
+// UNIT TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets() {
+        let mut histogram = Histogram::default();
+        histogram.record(Duration::from_nanos(500));
+        histogram.record(Duration::from_micros(1));
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_secs(1_000_000));
+
+        assert_eq!(histogram.buckets()[0], 1);
+        assert_eq!(histogram.buckets()[1], 1);
+        assert_eq!(histogram.buckets()[2], 1);
+        assert_eq!(histogram.buckets()[HISTOGRAM_BUCKETS - 1], 1);
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.max(), Duration::from_secs(1_000_000));
+    }
+
+    #[test]
+    fn histogram_percentile() {
+        let mut histogram = Histogram::default();
+        (0..99).for_each(|_| histogram.record(Duration::from_micros(10)));
+        histogram.record(Duration::from_millis(10));
+
+        assert_eq!(histogram.percentile(0.5), Duration::from_micros(16));
+        assert_eq!(histogram.percentile(1.0), Duration::from_millis(10));
+        assert_eq!(Histogram::default().percentile(0.5), Duration::ZERO);
+    }
+
+    #[test]
+    fn recorder_snapshot() {
+        let recorder = MetricsRecorder::default();
+        recorder.record("get_a", Duration::from_micros(5), Duration::from_micros(50), false);
+        recorder.record("get_a", Duration::from_micros(5), Duration::from_micros(50), true);
+
+        let metrics = recorder.snapshot();
+        assert_eq!(metrics.method("get_a").unwrap().calls, 2);
+        assert_eq!(metrics.method("get_a").unwrap().panics, 1);
+        assert!(metrics.method("inc_a").is_none());
+    }
+}
@@ -0,0 +1,61 @@
+// Routing for the generated <Class>WorkerPool and <Class>WorkerShards, which spread calls over several workers of
+// the same class.
+// Queue lengths are only looked up for LeastLoaded, so round-robin calls never touch the other workers.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Routing {
+    // Each call goes to the next worker in turn
+    #[default]
+    RoundRobin,
+    // Each call goes to the worker with the fewest queued messages (taking turns between equal ones)
+    LeastLoaded,
+}
+
+#[doc(hidden)]
+pub fn pick(routing: Routing, next: &AtomicUsize, size: usize, queue_len: impl Fn(usize) -> usize) -> usize {
+    let start = next.fetch_add(1, Ordering::Relaxed) % size;
+    match routing {
+        Routing::RoundRobin => start,
+        Routing::LeastLoaded => (start..start + size).map(|x| x % size).min_by_key(|x| queue_len(*x)).unwrap_or(start),
+    }
+}
+
+// The same key always picks the same worker (DefaultHasher::new() isn't randomly seeded)
+#[doc(hidden)]
+pub fn shard_index<K: Hash + ?Sized>(key: &K, size: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % size as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_robin() {
+        let next = AtomicUsize::new(0);
+        let picks: Vec<usize> = (0..5).map(|_| pick(Routing::RoundRobin, &next, 3, |x| [9, 0, 0][x])).collect();
+        assert_eq!(picks, [0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn least_loaded() {
+        let next = AtomicUsize::new(0);
+        assert_eq!(pick(Routing::LeastLoaded, &next, 3, |x| [3, 1, 2][x]), 1);
+        assert_eq!(pick(Routing::LeastLoaded, &next, 3, |x| [0, 0, 5][x]), 1);
+        assert_eq!(pick(Routing::LeastLoaded, &next, 3, |x| [0, 0, 5][x]), 0);
+    }
+
+    #[test]
+    fn shard_by_key() {
+        let shards: Vec<usize> = (0..100).map(|x| shard_index(&x, 4)).collect();
+        assert!(shards.iter().all(|x| *x < 4));
+        assert!((0..4).all(|x| shards.contains(&x)));
+        assert_eq!(shard_index("customer-7", 4), shard_index(&"customer-7".to_string(), 4));
+    }
+}
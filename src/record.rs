@@ -0,0 +1,108 @@
+// Message logs for #[worker(record)] services.
+// Each line of a log is one JSON Entry, written by the thread that sent the message.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry<M> {
+    // Microseconds since the Unix epoch
+    pub timestamp_us: u64,
+    // Name of the sending thread, or its id if it doesn't have one
+    pub thread: String,
+    pub message: M,
+}
+
+// What a worker's replay() did with the messages of a log (stop_thread isn't replayed or counted)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Replay {
+    // Method calls sent to the worker again
+    pub replayed: usize,
+    // Messages that can't be rebuilt from the log, like closures and streams
+    pub skipped: usize,
+}
+
+// Shared by every clone of a worker handle, so messages are logged in the order they reach the mailbox
+#[derive(Debug, Default)]
+pub struct Recorder {
+    log: Mutex<Option<File>>,
+}
+
+impl Recorder {
+    pub fn start(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let file = File::create(path)?;
+        *self.log.lock().expect("Worker recorder lock was poisoned") = Some(file);
+        Ok(())
+    }
+
+    pub fn stop(&self) -> std::io::Result<()> {
+        match self.log.lock().expect("Worker recorder lock was poisoned").take() {
+            Some(file) => file.sync_all(),
+            None => Ok(()),
+        }
+    }
+
+    // Held by the generated code while it sends the message that it logged
+    #[doc(hidden)]
+    pub fn lock(&self) -> RecorderGuard<'_> {
+        RecorderGuard(self.log.lock().expect("Worker recorder lock was poisoned"))
+    }
+}
+
+#[doc(hidden)]
+pub struct RecorderGuard<'a>(MutexGuard<'a, Option<File>>);
+
+impl RecorderGuard<'_> {
+    pub fn write(&mut self, message: impl FnOnce() -> serde_json::Value) {
+        let Some(file) = self.0.as_mut() else {
+            return;
+        };
+
+        let thread = std::thread::current();
+        let entry = Entry {
+            timestamp_us: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64,
+            thread: thread.name().map_or_else(|| format!("{:?}", thread.id()), str::to_string),
+            message: message(),
+        };
+        let mut line = serde_json::to_vec(&entry).expect("Failed to serialize recorded message");
+        line.push(b'\n');
+        file.write_all(&line).expect("Failed to write recorded message");
+    }
+}
+
+pub fn read<M: for<'de> Deserialize<'de>>(path: impl AsRef<Path>) -> std::io::Result<Vec<Entry<M>>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_common::TempPath;
+
+    #[test]
+    fn write_and_read() {
+        let path = TempPath::new("record.jsonl");
+        let recorder = Recorder::default();
+        recorder.lock().write(|| serde_json::json!("skipped"));
+        recorder.start(&path).unwrap();
+        recorder.lock().write(|| serde_json::json!({"inc_a": {"i": 3}}));
+        recorder.lock().write(|| serde_json::json!("exec"));
+        recorder.stop().unwrap();
+        recorder.lock().write(|| serde_json::json!("skipped"));
+
+        let entries = read::<serde_json::Value>(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].message["inc_a"]["i"], 3);
+        assert_eq!(entries[1].message, "exec");
+        assert!(entries[0].timestamp_us <= entries[1].timestamp_us);
+        assert_eq!(entries[0].thread, std::thread::current().name().unwrap());
+    }
+}
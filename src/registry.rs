@@ -0,0 +1,52 @@
+// Process-wide lookup of running workers by name.
+// Workers are registered by <Class>Worker::spawn_named(), and an entry is dropped as soon as its
+// worker thread has stopped (so get() never returns a handle to a stopped worker).
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, Weak};
+
+struct Entry {
+    worker: Box<dyn Any + Send + Sync>,
+    alive: Weak<()>,
+}
+
+static ENTRIES: Mutex<Option<HashMap<String, Entry>>> = Mutex::new(None);
+
+// The entries of workers that are still running
+fn entries() -> MutexGuard<'static, Option<HashMap<String, Entry>>> {
+    let mut entries = ENTRIES.lock().unwrap_or_else(|err| err.into_inner());
+    entries.get_or_insert_with(HashMap::new).retain(|_, entry| entry.alive.strong_count() > 0);
+    entries
+}
+
+pub struct Registry;
+
+impl Registry {
+    // Returns false (and doesn't register the worker) if a running worker already has the name
+    #[doc(hidden)]
+    pub fn register<W: Any + Send + Sync>(name: &str, worker: W, alive: Weak<()>) -> bool {
+        let mut entries = entries();
+        let entries = entries.get_or_insert_with(HashMap::new);
+        if entries.contains_key(name) {
+            return false;
+        }
+        entries.insert(name.to_string(), Entry { worker: Box::new(worker), alive });
+        true
+    }
+
+    // Returns None if no running worker has the name, or if it isn't a W
+    pub fn get<W: Any + Clone>(name: &str) -> Option<W> {
+        entries().as_ref()?.get(name)?.worker.downcast_ref::<W>().cloned()
+    }
+
+    pub fn contains(name: &str) -> bool {
+        entries().as_ref().is_some_and(|entries| entries.contains_key(name))
+    }
+
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = entries().iter().flat_map(|entries| entries.keys().cloned()).collect();
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,253 @@
+// Transport for remote workers.
+// Requests and responses are sent as frames (a big-endian u32 length, then that many bytes of JSON).
+// The generated Remote<Class>Worker proxies send requests through a Client, and <Class>Worker::serve_*
+// hands every request from a connection to the real worker, in the order they were sent.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+// Frames above this are refused, so a bad length can't make the reader allocate gigabytes
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+// Implemented by the generated response enums, so the client knows which call a response belongs to
+#[doc(hidden)]
+pub trait Correlated {
+    fn correlation_id(&self) -> u64;
+}
+
+pub fn write_frame<T: Serialize>(writer: &mut impl Write, value: &T) -> std::io::Result<()> {
+    let frame = serde_json::to_vec(value)?;
+    if frame.len() > MAX_FRAME_LEN {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Frame is too large"));
+    }
+    let len = frame.len() as u32;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+// Returns None when the other side closed the connection between frames
+pub fn read_frame<T: DeserializeOwned>(reader: &mut impl Read) -> std::io::Result<Option<T>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Frame is too large"));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(serde_json::from_slice(&frame)?))
+}
+
+// The writing half of a client's connection, which also closes the reading half (so the reader thread stops)
+#[doc(hidden)]
+pub trait Connection: Write + Send {
+    fn shutdown(&self) -> std::io::Result<()>;
+}
+
+impl Connection for std::net::TcpStream {
+    fn shutdown(&self) -> std::io::Result<()> {
+        std::net::TcpStream::shutdown(self, std::net::Shutdown::Both)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for std::os::unix::net::UnixStream {
+    fn shutdown(&self) -> std::io::Result<()> {
+        std::os::unix::net::UnixStream::shutdown(self, std::net::Shutdown::Both)
+    }
+}
+
+// None once the connection is closed
+type PendingCalls<Resp> = Arc<Mutex<Option<HashMap<u64, futures::channel::oneshot::Sender<Resp>>>>>;
+
+// The connection used by a remote worker proxy (shared by all of its clones)
+#[doc(hidden)]
+pub struct Client<Req, Resp> {
+    writer: Mutex<Box<dyn Connection>>,
+    pending: PendingCalls<Resp>,
+    next_correlation_id: AtomicU64,
+    _request: std::marker::PhantomData<fn(Req)>,
+}
+
+impl<Req: Serialize, Resp: DeserializeOwned + Correlated + Send + 'static> Client<Req, Resp> {
+    // Responses are read on their own thread, which stops when the connection is closed
+    pub fn new(mut reader: impl Read + Send + 'static, writer: impl Connection + 'static) -> Self {
+        let pending: PendingCalls<Resp> = Arc::new(Mutex::new(Some(HashMap::new())));
+        let reader_pending = Arc::clone(&pending);
+        std::thread::spawn(move || {
+            while let Ok(Some(response)) = read_frame::<Resp>(&mut reader) {
+                let mut pending = reader_pending.lock().expect("Remote worker lock was poisoned");
+                if let Some(send_ret) = pending.as_mut().and_then(|x| x.remove(&response.correlation_id())) {
+                    let _ = send_ret.send(response);
+                }
+            }
+            // Wakes up any callers that are still waiting, since their responses will never arrive
+            *reader_pending.lock().expect("Remote worker lock was poisoned") = None;
+        });
+
+        Client {
+            writer: Mutex::new(Box::new(writer)),
+            pending,
+            next_correlation_id: AtomicU64::new(0),
+            _request: std::marker::PhantomData,
+        }
+    }
+
+    pub fn send(&self, request: &Req) {
+        let mut writer = self.writer.lock().expect("Remote worker lock was poisoned");
+        write_frame(&mut *writer, request).expect("Failed to send request to remote worker");
+    }
+
+    pub fn call(&self, request: impl FnOnce(u64) -> Req) -> Resp {
+        let correlation_id = self.next_correlation_id.fetch_add(1, Ordering::Relaxed);
+        let (send_ret, recv_ret) = futures::channel::oneshot::channel();
+        match self.pending.lock().expect("Remote worker lock was poisoned").as_mut() {
+            Some(pending) => pending.insert(correlation_id, send_ret),
+            None => panic!("Remote worker is disconnected"),
+        };
+        self.send(&request(correlation_id));
+        match futures::executor::block_on(recv_ret) {
+            Ok(response) => response,
+            Err(_) => panic!("Remote worker disconnected before responding"),
+        }
+    }
+}
+
+// Closes the connection once the last clone of the proxy is dropped, so the server and the reader thread stop
+impl<Req, Resp> Drop for Client<Req, Resp> {
+    fn drop(&mut self) {
+        let _ = self.writer.get_mut().unwrap_or_else(PoisonError::into_inner).shutdown();
+    }
+}
+
+// Handles every request from one connection, until it's closed
+pub fn serve_connection<Req: DeserializeOwned, Resp: Serialize>(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    handle_request: impl Fn(Req) -> Option<Resp>,
+) -> std::io::Result<()> {
+    while let Some(request) = read_frame(&mut reader)? {
+        if let Some(response) = handle_request(request) {
+            write_frame(&mut writer, &response)?;
+        }
+    }
+    Ok(())
+}
+
+// Serves each connection on its own thread, until the listener fails
+fn serve_incoming<S: Read + Write + Send + 'static, Req: DeserializeOwned, Resp: Serialize>(
+    incoming: impl Iterator<Item = std::io::Result<S>>,
+    try_clone: impl Fn(&S) -> std::io::Result<S>,
+    handle_request: impl Fn(Req) -> Option<Resp> + Clone + Send + 'static,
+) {
+    for stream in incoming {
+        let Ok(stream) = stream else {
+            break;
+        };
+        let Ok(reader) = try_clone(&stream) else {
+            continue;
+        };
+        let handle_request = handle_request.clone();
+        std::thread::spawn(move || serve_connection(reader, stream, handle_request));
+    }
+}
+
+pub fn serve_tcp<Req: DeserializeOwned, Resp: Serialize>(
+    listener: std::net::TcpListener,
+    handle_request: impl Fn(Req) -> Option<Resp> + Clone + Send + 'static,
+) {
+    let incoming = listener.incoming().map(|stream| {
+        let stream = stream?;
+        stream.set_nodelay(true)?;
+        Ok(stream)
+    });
+    serve_incoming(incoming, std::net::TcpStream::try_clone, handle_request);
+}
+
+#[cfg(unix)]
+pub fn serve_unix<Req: DeserializeOwned, Resp: Serialize>(
+    listener: std::os::unix::net::UnixListener,
+    handle_request: impl Fn(Req) -> Option<Resp> + Clone + Send + 'static,
+) {
+    serve_incoming(listener.incoming(), std::os::unix::net::UnixStream::try_clone, handle_request);
+}
+
+pub fn connect_tcp<Req: Serialize, Resp: DeserializeOwned + Correlated + Send + 'static>(
+    addr: impl std::net::ToSocketAddrs,
+) -> std::io::Result<Client<Req, Resp>> {
+    let stream = std::net::TcpStream::connect(addr)?;
+    stream.set_nodelay(true)?;
+    Ok(Client::new(stream.try_clone()?, stream))
+}
+
+#[cfg(unix)]
+pub fn connect_unix<Req: Serialize, Resp: DeserializeOwned + Correlated + Send + 'static>(
+    path: impl AsRef<std::path::Path>,
+) -> std::io::Result<Client<Req, Resp>> {
+    let stream = std::os::unix::net::UnixStream::connect(path)?;
+    Ok(Client::new(stream.try_clone()?, stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, &"hello").unwrap();
+        write_frame(&mut buffer, &vec![1, 2, 3]).unwrap();
+        assert_eq!(&buffer[..4], &7u32.to_be_bytes());
+
+        let mut reader = &buffer[..];
+        assert_eq!(read_frame::<String>(&mut reader).unwrap().unwrap(), "hello");
+        assert_eq!(read_frame::<Vec<i32>>(&mut reader).unwrap().unwrap(), [1, 2, 3]);
+        assert!(read_frame::<String>(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn frame_too_large() {
+        let mut reader = &u32::MAX.to_be_bytes()[..];
+        let err = read_frame::<String>(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let err = write_frame(&mut Vec::new(), &"x".repeat(MAX_FRAME_LEN)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Echo(u64);
+
+    impl Correlated for Echo {
+        fn correlation_id(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn drop_closes_connection() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (send_done, recv_done) = crossbeam_channel::bounded(1);
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let served = serve_connection(stream.try_clone().unwrap(), stream, |request: u64| Some(Echo(request)));
+            send_done.send(served.is_ok()).unwrap();
+        });
+
+        let client = connect_tcp::<u64, Echo>(addr).unwrap();
+        assert_eq!(client.call(|correlation_id| correlation_id).0, 0);
+        drop(client);
+        // The server only stops serving the connection once it's closed
+        assert!(recv_done.recv_timeout(std::time::Duration::from_secs(5)).unwrap());
+    }
+}
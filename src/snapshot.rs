@@ -0,0 +1,31 @@
+// Snapshots of the state of #[worker(snapshot)] services.
+// A snapshot is the state serialized as JSON, so it can be written to disk and restored by a later process.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Fails (instead of panicking on the worker thread) if the state can't be serialized as JSON
+#[doc(hidden)]
+pub fn to_bytes<T: Serialize>(state: &T) -> std::io::Result<Vec<u8>> {
+    Ok(serde_json::to_vec(state)?)
+}
+
+pub fn from_bytes<T: DeserializeOwned>(snapshot: &[u8]) -> std::io::Result<T> {
+    Ok(serde_json::from_slice(snapshot)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let snapshot = to_bytes(&(3, vec!["a".to_string()])).unwrap();
+        assert_eq!(from_bytes::<(i32, Vec<String>)>(&snapshot).unwrap(), (3, vec!["a".to_string()]));
+        let err = from_bytes::<(i32, Vec<String>)>(b"[\"a\", []]").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let err = to_bytes(&std::collections::HashMap::from([((1, 2), 3)])).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
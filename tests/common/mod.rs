@@ -0,0 +1,35 @@
+// Helpers shared by the integration tests (and the unit tests in src, which include this file by path)
+
+use std::path::{Path, PathBuf};
+
+// A file in the temp dir that's removed when the test ends, even if it fails
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    // The process id keeps concurrent test runs apart, and a file left by an earlier run is removed
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("nano_services_{}_{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        TempPath(path)
+    }
+}
+
+impl std::ops::Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
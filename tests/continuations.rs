@@ -0,0 +1,82 @@
+use std::sync::mpsc;
+
+mod prices {
+    use nano_services::*;
+
+    pub struct Prices {}
+
+    #[worker]
+    impl Prices {
+        pub fn new() -> Prices {
+            Prices {}
+        }
+
+        #[blocking_method]
+        pub fn price(&self, item: String, quantity: u32) -> u32 {
+            item.len() as u32 * quantity
+        }
+    }
+}
+
+mod cart {
+    use super::prices::PricesWorker;
+    use nano_services::*;
+
+    pub struct Cart {
+        prices: PricesWorker,
+        pub total: u32,
+    }
+
+    #[worker]
+    impl Cart {
+        pub fn new(prices: PricesWorker) -> Cart {
+            Cart { prices, total: 0 }
+        }
+
+        // Asks for the price without waiting, and adds it to the total once it arrives
+        pub fn add(&self, item: String, quantity: u32) {
+            let this = Registry::get::<CartWorker>("continuations_cart").unwrap();
+            self.prices.price_then(item, quantity, move |price| {
+                this.exec(move |cart: &mut Cart| cart.total += price)
+            });
+        }
+
+        #[blocking_method]
+        pub fn total(&self) -> u32 {
+            self.total
+        }
+    }
+}
+
+use cart::CartWorker;
+use prices::PricesWorker;
+
+#[test]
+fn then_runs_on_worker_thread() {
+    let (handle, prices) = PricesWorker::new();
+    let (send, recv) = mpsc::channel();
+    prices.price_then("pear".to_string(), 2, move |price| {
+        send.send((price, std::thread::current().id())).unwrap()
+    });
+    assert_eq!(recv.recv().unwrap(), (8, prices.thread_id()));
+    prices.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn then_delivers_to_another_worker() {
+    let (prices_handle, prices) = PricesWorker::new();
+    let (cart_handle, cart) = CartWorker::spawn_named("continuations_cart", prices.clone());
+    cart.add("fig".to_string(), 1);
+    cart.add("plum".to_string(), 3);
+
+    // Wait for the cart to ask for the prices, then for the prices worker to reply, then for the cart to add them
+    cart.total();
+    prices.price(String::new(), 0);
+    assert_eq!(cart.total(), 15);
+
+    cart.stop_thread();
+    cart_handle.join().unwrap();
+    prices.stop_thread();
+    prices_handle.join().unwrap();
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "deadlock_detection")]
+
+use nano_services::*;
+
+struct Node {
+    value: i32,
+}
+
+#[worker]
+impl Node {
+    pub fn new(value: i32) -> Node {
+        Node { value }
+    }
+
+    #[blocking_method]
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+}
+
+fn panic_message(result: std::thread::Result<()>) -> String {
+    let payload = result.unwrap_err();
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().unwrap().to_string(),
+    }
+}
+
+#[test]
+fn detect_cycle_between_workers() {
+    let (a_handle, a) = NodeWorker::new(1);
+    let (b_handle, b) = NodeWorker::new(2);
+
+    // a waits on b, which waits on a (from b's thread)
+    let (a_clone, b_clone) = (a.clone(), b.clone());
+    let caller = std::thread::spawn(move || {
+        a_clone.query(move |_: &Node| b_clone.query(move |_: &Node| a.value()))
+    });
+
+    let message = panic_message(b_handle.join());
+    assert!(message.starts_with("Deadlock detected"), "{message}");
+    assert!(message.contains("Node::value"), "{message}");
+    assert!(message.contains("Node::query"), "{message}");
+    assert!(caller.join().is_err());
+    assert!(a_handle.join().is_err());
+}
+
+#[test]
+fn no_false_positive_for_chains() {
+    let (a_handle, a) = NodeWorker::new(1);
+    let (b_handle, b) = NodeWorker::new(2);
+    let b_clone = b.clone();
+    assert_eq!(a.query(move |a: &Node| a.value + b_clone.value()), 3);
+    assert_eq!(a.value(), 1);
+    a.stop_thread();
+    a_handle.join().unwrap();
+    b.stop_thread();
+    b_handle.join().unwrap();
+}
@@ -0,0 +1,211 @@
+#![cfg(feature = "event_log")]
+
+mod common;
+
+use common::TempPath;
+use nano_services::serde::{Deserialize, Serialize};
+use nano_services::*;
+
+use std::path::Path;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "nano_services::serde")]
+struct Account {
+    owner: String,
+    balance: i64,
+    views: u32,
+}
+
+#[worker(event_log)]
+impl Account {
+    pub fn new(owner: String) -> Account {
+        Account {
+            owner,
+            balance: 0,
+            views: 0,
+        }
+    }
+
+    pub fn deposit(&mut self, amount: i64) {
+        self.balance += amount;
+    }
+
+    pub fn transfer_to(&mut self, owner: String) {
+        self.owner = owner;
+    }
+
+    // Logged like the non-blocking methods (the return value doesn't matter when the log is read again)
+    #[blocking_method]
+    pub fn withdraw(&mut self, amount: i64) -> bool {
+        let allowed = amount <= self.balance;
+        if allowed {
+            self.balance -= amount;
+        }
+        allowed
+    }
+
+    // Doesn't change the state, so it isn't logged
+    pub fn view(&self) {}
+
+    #[blocking_method]
+    pub fn summary(&self) -> (String, i64) {
+        (self.owner.clone(), self.balance)
+    }
+}
+
+fn log_lines(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+}
+
+#[test]
+fn rebuild_from_log() {
+    let path = TempPath::new("rebuild_from_log.jsonl");
+    let (handle, account) = AccountWorker::open(&path, "ann".to_string()).unwrap();
+    account.deposit(10);
+    account.view();
+    account.transfer_to("bob".to_string());
+    account.deposit(-3);
+    account.stop_thread();
+    handle.join().unwrap();
+    assert_eq!(log_lines(&path), [r#"{"deposit":{"amount":10}}"#, r#"{"transfer_to":{"owner":"bob"}}"#, r#"{"deposit":{"amount":-3}}"#]);
+
+    // The constructor args only matter for a new log
+    let (handle, account) = AccountWorker::open(&path, "ann".to_string()).unwrap();
+    assert_eq!(account.summary(), ("bob".to_string(), 7));
+    account.deposit(1);
+    account.stop_thread();
+    handle.join().unwrap();
+
+    let (handle, account) = AccountWorker::open(&path, "ann".to_string()).unwrap();
+    assert_eq!(account.summary(), ("bob".to_string(), 8));
+    account.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn rebuild_blocking_calls() {
+    let path = TempPath::new("rebuild_blocking_calls.jsonl");
+    let (handle, account) = AccountWorker::open(&path, "gus".to_string()).unwrap();
+    account.deposit(10);
+    assert!(account.withdraw(4));
+    assert!(!account.withdraw(40));
+    let (send_allowed, recv_allowed) = std::sync::mpsc::channel();
+    account.withdraw_then(1, move |allowed| send_allowed.send(allowed).unwrap());
+    assert!(recv_allowed.recv().unwrap());
+    account.stop_thread();
+    handle.join().unwrap();
+    assert_eq!(log_lines(&path)[1..], [r#"{"withdraw":{"amount":4}}"#, r#"{"withdraw":{"amount":40}}"#, r#"{"withdraw":{"amount":1}}"#]);
+
+    let (handle, account) = AccountWorker::open(&path, "gus".to_string()).unwrap();
+    assert_eq!(account.summary(), ("gus".to_string(), 5));
+    account.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn compact_into_snapshot() {
+    let path = TempPath::new("compact_into_snapshot.jsonl");
+    let (handle, account) = AccountWorker::open(&path, "cat".to_string()).unwrap();
+    account.exec(|account: &mut Account| account.views = 5);
+    for _ in 0..100 {
+        account.deposit(1);
+    }
+    account.compact().unwrap();
+    account.deposit(50);
+    account.stop_thread();
+    handle.join().unwrap();
+    assert_eq!(
+        log_lines(&path),
+        [r#"{"snapshot":{"owner":"cat","balance":100,"views":5}}"#, r#"{"deposit":{"amount":50}}"#]
+    );
+
+    let (handle, account) = AccountWorker::open(&path, "dan".to_string()).unwrap();
+    assert_eq!(account.summary(), ("cat".to_string(), 150));
+    assert_eq!(account.query(|account: &Account| account.views), 5);
+    account.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn new_without_log() {
+    let (handle, account) = AccountWorker::new("eve".to_string());
+    account.deposit(2);
+    account.compact().unwrap();
+    assert_eq!(account.summary(), ("eve".to_string(), 2));
+    account.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn open_invalid_log() {
+    let path = TempPath::new("open_invalid_log.jsonl");
+    std::fs::write(&path, "{\"refund\":{\"amount\":1}}\n").unwrap();
+    let err = AccountWorker::open(&path, "fay".to_string()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "record")]
+mod counter {
+    use nano_services::serde::{Deserialize, Serialize};
+    use nano_services::*;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "nano_services::serde")]
+    pub struct Counter {
+        pub count: u32,
+    }
+
+    #[worker(record, event_log)]
+    impl Counter {
+        pub fn new() -> Counter {
+            Counter { count: 0 }
+        }
+
+        pub fn add(&mut self, count: u32) {
+            self.count += count;
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "record")]
+fn record_compact() {
+    use counter::{CounterWorker, CounterWorkerMessage};
+
+    let path = TempPath::new("record_compact.jsonl");
+    let (handle, counter) = CounterWorker::new();
+    counter.start_recording(&path).unwrap();
+    counter.add(2);
+    counter.compact().unwrap();
+    counter.stop_recording().unwrap();
+    counter.stop_thread();
+    handle.join().unwrap();
+
+    let entries = record::read::<CounterWorkerMessage>(&path).unwrap();
+    assert!(matches!(entries[0].message, CounterWorkerMessage::Add { count: 2 }));
+    assert!(matches!(entries[1].message, CounterWorkerMessage::Compact));
+
+    let (handle, counter) = CounterWorker::new();
+    assert_eq!(counter.replay(&path).unwrap(), record::Replay { replayed: 1, skipped: 1 });
+    assert_eq!(counter.query(|counter: &counter::Counter| counter.count), 2);
+    counter.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg(feature = "record")]
+fn record_compact_concurrently() {
+    use counter::CounterWorker;
+
+    let log = TempPath::new("record_compact_concurrently.jsonl");
+    let recording = TempPath::new("record_compact_concurrently_recording.jsonl");
+    let (handle, counter) = CounterWorker::open(&log).unwrap();
+    counter.start_recording(&recording).unwrap();
+    let adder = counter.clone();
+    let adds = std::thread::spawn(move || (0..500).for_each(|_| adder.add(1)));
+    (0..500).for_each(|_| counter.compact().unwrap());
+    adds.join().unwrap();
+    assert_eq!(counter.query(|counter: &counter::Counter| counter.count), 500);
+    counter.stop_thread();
+    handle.join().unwrap();
+}
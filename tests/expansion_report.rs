@@ -0,0 +1,59 @@
+use nano_services::*;
+
+use std::path::PathBuf;
+
+struct Reporter {
+    reports: u32,
+}
+
+#[worker(debug)]
+impl Reporter {
+    pub fn new() -> Reporter {
+        Reporter { reports: 0 }
+    }
+
+    pub fn report(&mut self) {
+        self.reports += 1;
+    }
+
+    #[blocking_method]
+    pub fn reports(&self) -> u32 {
+        self.reports
+    }
+}
+
+fn report_path() -> PathBuf {
+    let target_dir = option_env!("NANO_SERVICES_EXPAND")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            option_env!("CARGO_TARGET_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target"))
+                .join("nano_services")
+        });
+    // The report is named after the line of #[worker], so it's found by prefix rather than rebuilt here
+    std::fs::read_dir(target_dir)
+        .unwrap()
+        .map(|x| x.unwrap().path())
+        .find(|x| {
+            let name = x.file_name().unwrap().to_string_lossy();
+            name.starts_with("ReporterWorker_tests_expansion_report_") && name.ends_with(".rs")
+        })
+        .unwrap()
+}
+
+#[test]
+fn worker_debug_writes_expansion_report() {
+    let report = std::fs::read_to_string(report_path()).unwrap();
+    assert!(report.starts_with("// Expansion report for ReporterWorker"));
+    assert!(report.contains("// new                      no        yes     -"));
+    assert!(report.contains("// report                   no        no      Report"));
+    assert!(report.contains("// reports                  yes       no      Reports"));
+    assert!(report.contains("struct ReporterWorker"));
+
+    let (handle, reporter) = ReporterWorker::new();
+    reporter.report();
+    assert_eq!(reporter.reports(), 1);
+    reporter.stop_thread();
+    handle.join().unwrap();
+}
@@ -0,0 +1,87 @@
+use nano_services::*;
+
+#[derive(Debug, PartialEq)]
+struct Item {
+    name: String,
+}
+
+impl From<&'static str> for Item {
+    fn from(name: &'static str) -> Item {
+        Item {
+            name: name.to_string(),
+        }
+    }
+}
+
+struct Bag {
+    items: Vec<Item>,
+}
+
+#[worker]
+impl Bag {
+    pub fn new() -> Bag {
+        Bag { items: Vec::new() }
+    }
+
+    pub fn add(&mut self, item: impl Into<Item>) {
+        self.items.push(item.into());
+    }
+
+    pub fn apply<F: FnOnce(&mut Self) + Send>(&mut self, f: F) {
+        f(self);
+    }
+
+    #[blocking_method]
+    pub fn len_as<T>(&self) -> T
+    where
+        T: From<u8>,
+    {
+        T::from(self.items.len() as u8)
+    }
+
+    #[blocking_method]
+    pub fn names_with<const N: usize>(&self, suffix: impl ToString) -> Vec<String> {
+        self.items
+            .iter()
+            .take(N)
+            .map(|item| item.name.clone() + &suffix.to_string())
+            .collect()
+    }
+}
+
+#[test]
+fn worker_impl_trait_arg() {
+    let (handle, bag) = BagWorker::new();
+    bag.add("apple");
+    bag.add(Item {
+        name: "pear".to_string(),
+    });
+    assert_eq!(bag.len_as::<u32>(), 2);
+    bag.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn worker_generic_closure_arg() {
+    let (handle, bag) = BagWorker::new();
+    bag.add("apple");
+    bag.apply(|bag: &mut Bag| bag.items.clear());
+    bag.add("pear");
+    assert_eq!(bag.names_with::<4>("!"), vec!["pear!".to_string()]);
+    bag.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn worker_generic_const_param() {
+    let (handle, bag) = BagWorker::new();
+    bag.add("apple");
+    bag.add("pear");
+    bag.add("plum");
+    assert_eq!(
+        bag.names_with::<2>(1),
+        vec!["apple1".to_string(), "pear1".to_string()]
+    );
+    bag.stop_thread();
+    handle.join().unwrap();
+}
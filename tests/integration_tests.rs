@@ -142,3 +142,107 @@ fn threading() {
     thingy.stop_thread();
     thingy_handle.join().unwrap();
 }
+
+#[test]
+fn worker_exec() {
+    let counter = Arc::new(Mutex::new(-1));
+    let (handle, thingy) = ThingyWorker::new(Arc::clone(&counter));
+    thingy.exec(|thingy: &mut Thingy| *thingy.a.lock().unwrap() = 5);
+    thingy.stop_thread();
+    handle.join().unwrap();
+
+    assert_eq!(*counter.lock().unwrap(), 5);
+}
+
+#[test]
+fn worker_query() {
+    let counter = Arc::new(Mutex::new(-1));
+    let (handle, thingy) = ThingyWorker::new(Arc::clone(&counter));
+    assert_eq!(thingy.query(|thingy: &Thingy| thingy.get_a() + 1), 1);
+    thingy.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn worker_exec_and_query_ordering() {
+    let counter = Arc::new(Mutex::new(-1));
+    let (handle, thingy) = ThingyWorker::new(Arc::clone(&counter));
+    thingy.inc_a(3);
+    thingy.exec(|thingy: &mut Thingy| *thingy.a.lock().unwrap() *= 2);
+    thingy.inc_a(1);
+    assert_eq!(thingy.query(|thingy: &Thingy| *thingy.a.lock().unwrap()), 7);
+    thingy.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn worker_queue_len() {
+    let counter = Arc::new(Mutex::new(-1));
+    let (handle, thingy) = ThingyWorker::new(Arc::clone(&counter));
+    let (started_send, started_recv) = std::sync::mpsc::channel();
+    let (release_send, release_recv) = std::sync::mpsc::channel::<()>();
+    thingy.exec(move |_: &mut Thingy| {
+        started_send.send(()).unwrap();
+        release_recv.recv().unwrap();
+    });
+    started_recv.recv().unwrap();
+    thingy.inc_a(1);
+    thingy.inc_a(2);
+    assert_eq!(thingy.queue_len(), 2);
+    release_send.send(()).unwrap();
+    assert_eq!(thingy.get_a(), 3);
+    assert_eq!(thingy.queue_len(), 0);
+    thingy.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn worker_is_alive() {
+    let counter = Arc::new(Mutex::new(-1));
+    let (handle, thingy) = ThingyWorker::new(Arc::clone(&counter));
+    assert!(thingy.is_alive());
+    thingy.stop_thread();
+    handle.join().unwrap();
+    assert!(!thingy.is_alive());
+}
+
+#[test]
+fn worker_thread_id() {
+    let counter = Arc::new(Mutex::new(-1));
+    let (handle, thingy) = ThingyWorker::new(Arc::clone(&counter));
+    assert_eq!(thingy.thread_id(), handle.thread().id());
+    assert_eq!(
+        thingy.query(|_: &Thingy| std::thread::current().id()),
+        thingy.thread_id()
+    );
+    thingy.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn worker_messages_processed_and_uptime() {
+    let counter = Arc::new(Mutex::new(-1));
+    let (handle, thingy) = ThingyWorker::new(Arc::clone(&counter));
+    thingy.inc_a(3);
+    assert_eq!(thingy.get_a(), 3);
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    thingy.stop_thread();
+    handle.join().unwrap();
+
+    assert_eq!(thingy.messages_processed(), 2);
+    assert!(thingy.uptime() >= std::time::Duration::from_millis(10));
+}
+
+#[test]
+fn worker_blocking_call_from_own_thread() {
+    let counter = Arc::new(Mutex::new(-1));
+    let (handle, thingy) = ThingyWorker::new(Arc::clone(&counter));
+    let thingy_clone = thingy.clone();
+    thingy.exec(move |_: &mut Thingy| {
+        thingy_clone.get_a();
+    });
+    let payload = handle.join().unwrap_err();
+    let message = payload.downcast_ref::<&str>().unwrap();
+    assert!(message.starts_with("ThingyWorker::get_a is a blocking call from ThingyWorker's own thread"));
+    assert!(!thingy.is_alive());
+}
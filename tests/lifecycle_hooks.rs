@@ -0,0 +1,120 @@
+use nano_services::*;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+struct Journal {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+#[worker]
+impl Journal {
+    pub fn new(events: Arc<Mutex<Vec<String>>>) -> Journal {
+        Journal { events }
+    }
+
+    pub fn record(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    pub fn fail(&self) {
+        panic!("boom");
+    }
+
+    #[on_start]
+    fn start(&self) {
+        self.record("start".to_string());
+    }
+
+    #[on_stop]
+    fn stop(&self) {
+        self.record("stop".to_string());
+    }
+
+    #[on_idle]
+    fn idle(&self) {
+        self.record("idle".to_string());
+    }
+
+    #[on_panic]
+    fn panicked(&self, payload: &(dyn std::any::Any + Send)) {
+        let message = payload.downcast_ref::<&str>().unwrap_or(&"unknown");
+        self.record(format!("panic: {message}"));
+    }
+}
+
+fn events_without_idle(events: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+    let events = events.lock().unwrap();
+    events.iter().filter(|x| *x != "idle").cloned().collect()
+}
+
+#[test]
+fn worker_on_start_and_on_stop() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let (handle, journal) = JournalWorker::new(Arc::clone(&events));
+    journal.record("work".to_string());
+    journal.stop_thread();
+    handle.join().unwrap();
+
+    assert_eq!(events_without_idle(&events), vec!["start", "work", "stop"]);
+}
+
+#[test]
+fn worker_on_idle() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let (handle, journal) = JournalWorker::new(Arc::clone(&events));
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(*events.lock().unwrap(), vec!["start"]);
+    journal.record("work".to_string());
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(*events.lock().unwrap(), vec!["start", "work", "idle"]);
+    journal.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn worker_on_panic() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let (handle, journal) = JournalWorker::new(Arc::clone(&events));
+    journal.fail();
+    assert!(handle.join().is_err());
+
+    assert_eq!(events_without_idle(&events), vec!["start", "panic: boom"]);
+}
+
+mod ticker {
+    use nano_services::*;
+
+    use std::sync::{Arc, Mutex};
+
+    pub struct Ticker {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[worker]
+    impl Ticker {
+        pub fn new(events: Arc<Mutex<Vec<String>>>) -> Ticker {
+            Ticker { events }
+        }
+
+        #[periodic(every_ms = 5)]
+        fn tick(&self) {
+            panic!("tick");
+        }
+
+        #[on_panic]
+        fn panicked(&self, payload: &(dyn std::any::Any + Send)) {
+            let message = payload.downcast_ref::<&str>().unwrap_or(&"unknown");
+            self.events.lock().unwrap().push(format!("panic: {message}"));
+        }
+    }
+}
+
+#[test]
+fn worker_on_panic_in_periodic() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let (handle, _ticker) = ticker::TickerWorker::new(Arc::clone(&events));
+    assert!(handle.join().is_err());
+
+    assert_eq!(*events.lock().unwrap(), vec!["panic: tick"]);
+}
@@ -0,0 +1,77 @@
+use nano_services::*;
+
+use std::collections::HashMap;
+
+struct Router {
+    routes: HashMap<String, String>,
+    version: u32,
+    log: Vec<String>,
+}
+
+#[worker]
+impl Router {
+    pub fn new(routes: Vec<(String, String)>) -> Router {
+        Router {
+            routes: routes.into_iter().collect(),
+            version: 1,
+            log: Vec::new(),
+        }
+    }
+
+    pub fn visit(&mut self, path: String) {
+        let target = self.routes.get(&path).cloned().unwrap_or_else(|| "404".to_string());
+        self.log.push(format!("v{} {path} -> {target}", self.version));
+    }
+
+    #[blocking_method]
+    pub fn log(&self) -> Vec<String> {
+        self.log.clone()
+    }
+
+    // Rebuilds the routes from a new config, keeping the log
+    fn reload(self, routes: Vec<(String, String)>) -> Router {
+        Router {
+            routes: routes.into_iter().collect(),
+            version: self.version + 1,
+            log: self.log,
+        }
+    }
+}
+
+fn routes(target: &str) -> Vec<(String, String)> {
+    vec![("/home".to_string(), target.to_string())]
+}
+
+#[test]
+fn upgrade_in_mailbox_order() {
+    let (handle, router) = RouterWorker::new(routes("old"));
+    let client = router.clone();
+    router.exec(|_: &mut Router| std::thread::sleep(std::time::Duration::from_millis(20)));
+    client.visit("/home".to_string());
+    router.upgrade(|router: Router| router.reload(routes("new")));
+    client.visit("/home".to_string());
+    client.visit("/away".to_string());
+    assert_eq!(client.log(), ["v1 /home -> old", "v2 /home -> new", "v2 /away -> 404"]);
+    router.stop_thread();
+    handle.join().unwrap();
+    assert_eq!(router.messages_processed(), 6);
+}
+
+#[test]
+fn upgrade_with_driver() {
+    let (mut driver, router) = RouterWorker::new_manual(routes("old"));
+    router.upgrade(|router: Router| router.reload(routes("new")));
+    router.visit("/home".to_string());
+    assert_eq!(driver.step_all(), 2);
+    assert_eq!(driver.state().version, 2);
+    assert_eq!(driver.state().log, ["v2 /home -> new"]);
+}
+
+#[test]
+#[should_panic(expected = "The worker's state was lost when an upgrade panicked")]
+fn panicked_upgrade_with_driver() {
+    let (mut driver, router) = RouterWorker::new_manual(routes("old"));
+    router.upgrade(|_: Router| panic!("bad config"));
+    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| driver.step()));
+    driver.state();
+}
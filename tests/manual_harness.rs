@@ -0,0 +1,153 @@
+mod counter {
+    use nano_services::*;
+
+    pub struct Counter {
+        pub count: i32,
+        pub log: Vec<String>,
+    }
+
+    #[worker]
+    impl Counter {
+        pub fn new(count: i32) -> Counter {
+            Counter {
+                count,
+                log: Vec::new(),
+            }
+        }
+
+        pub fn add(&mut self, i: i32) {
+            self.count += i;
+            self.log.push(format!("add {i}"));
+        }
+
+        #[blocking_method]
+        pub fn get(&self) -> i32 {
+            self.count
+        }
+    }
+}
+
+mod relay {
+    use super::counter::{Counter, CounterWorker};
+    use nano_services::*;
+
+    pub struct Relay {
+        target: CounterWorker,
+    }
+
+    #[worker]
+    impl Relay {
+        pub fn new(target: CounterWorker) -> Relay {
+            Relay { target }
+        }
+
+        pub fn forward(&self, i: i32) {
+            self.target.add(i * 10);
+        }
+
+        pub fn log_count(&self) {
+            self.target.exec(|counter: &mut Counter| {
+                let count = counter.count;
+                counter.log.push(format!("count {count}"));
+            });
+        }
+    }
+}
+
+use counter::{Counter, CounterWorker};
+use relay::RelayWorker;
+
+#[test]
+fn manual_messages_wait_for_step() {
+    let (mut driver, counter) = CounterWorker::new_manual(1);
+    counter.add(2);
+    counter.add(3);
+    assert_eq!(driver.pending(), 2);
+    assert_eq!(driver.state().count, 1);
+
+    assert!(driver.step());
+    assert_eq!(driver.state().count, 3);
+    assert_eq!(driver.pending(), 1);
+
+    assert_eq!(driver.step_all(), 1);
+    assert_eq!(driver.state().count, 6);
+    assert!(!driver.step());
+    assert_eq!(counter.messages_processed(), 2);
+    assert_eq!(counter.thread_id(), std::thread::current().id());
+}
+
+#[test]
+fn manual_stop_thread() {
+    let (mut driver, counter) = CounterWorker::new_manual(0);
+    assert!(counter.is_alive());
+    counter.add(1);
+    counter.stop_thread();
+    counter.add(2);
+    assert_eq!(driver.step_all(), 2);
+    assert!(!counter.is_alive());
+    assert_eq!(driver.state().count, 1);
+    assert!(!driver.step());
+}
+
+#[test]
+fn manual_interleaving_between_services() {
+    let (mut counter_driver, counter) = CounterWorker::new_manual(0);
+    let (mut relay_driver, relay) = RelayWorker::new_manual(counter.clone());
+
+    relay.forward(1);
+    counter.add(2);
+    relay.log_count();
+
+    // Run the counter's own message first, then let the relay send its messages
+    counter_driver.step_all();
+    relay_driver.step_all();
+    counter_driver.step_all();
+    assert_eq!(counter_driver.state().log, ["add 2", "add 10", "count 12"]);
+
+    // Same messages, but the relay runs first this time
+    relay.forward(1);
+    counter.add(2);
+    relay.log_count();
+    relay_driver.step_all();
+    counter_driver.step_all();
+    assert_eq!(
+        counter_driver.state().log[3..],
+        ["add 2", "add 10", "count 24"]
+    );
+}
+
+#[test]
+fn manual_blocking_call_from_another_thread() {
+    let (mut driver, counter) = CounterWorker::new_manual(4);
+    let caller = std::thread::spawn(move || counter.get());
+    while driver.pending() == 0 {
+        std::thread::yield_now();
+    }
+    driver.step();
+    assert_eq!(caller.join().unwrap(), 4);
+}
+
+#[test]
+fn manual_send_after_waits_for_deadline() {
+    let (mut driver, counter) = CounterWorker::new_manual(0);
+    counter.send_after(
+        std::time::Duration::from_millis(20),
+        |counter: &mut Counter| counter.add(5),
+    );
+    driver.step_all();
+    assert_eq!(driver.state().count, 0);
+    assert_eq!(counter.messages_processed(), 0);
+    std::thread::sleep(std::time::Duration::from_millis(30));
+    driver.step_all();
+    assert_eq!(driver.state().count, 5);
+    assert_eq!(counter.messages_processed(), 1);
+    driver.state_mut().count = 0;
+    assert_eq!(driver.state().count, 0);
+}
+
+#[test]
+#[should_panic(expected = "CounterWorker::get is a blocking call from CounterWorker's own thread")]
+fn manual_blocking_call_from_same_thread() {
+    let (_driver, counter) = CounterWorker::new_manual(4);
+    counter.get();
+}
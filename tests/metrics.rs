@@ -0,0 +1,97 @@
+#![cfg(feature = "metrics")]
+
+use nano_services::*;
+
+use std::time::Duration;
+
+struct Sleeper {
+    naps: u32,
+}
+
+#[worker]
+impl Sleeper {
+    pub fn new() -> Sleeper {
+        Sleeper { naps: 0 }
+    }
+
+    pub fn nap(&mut self, millis: u64) {
+        std::thread::sleep(Duration::from_millis(millis));
+        self.naps += 1;
+    }
+
+    pub fn fail(&self) {
+        panic!("fail");
+    }
+
+    #[blocking_method]
+    pub fn naps(&self) -> u32 {
+        self.naps
+    }
+}
+
+#[test]
+fn worker_metrics_calls_and_timings() {
+    let (handle, sleeper) = SleeperWorker::new();
+    sleeper.nap(20);
+    sleeper.nap(1);
+    assert_eq!(sleeper.naps(), 2);
+    sleeper.stop_thread();
+    handle.join().unwrap();
+
+    let metrics = sleeper.metrics();
+    let nap = metrics.method("nap").unwrap();
+    assert_eq!(nap.calls, 2);
+    assert_eq!(nap.panics, 0);
+    assert!(nap.handler_time.max() >= Duration::from_millis(20));
+    assert!(nap.queue_wait.max() >= Duration::from_millis(15));
+    assert_eq!(metrics.method("naps").unwrap().calls, 1);
+    assert_eq!(metrics.method("stop_thread").unwrap().calls, 1);
+    assert!(metrics.method("fail").is_none());
+}
+
+#[test]
+fn worker_metrics_send_after() {
+    let (handle, sleeper) = SleeperWorker::new();
+    sleeper.send_after(Duration::from_millis(30), |sleeper: &mut Sleeper| sleeper.naps += 1);
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(sleeper.naps(), 1);
+    sleeper.stop_thread();
+    handle.join().unwrap();
+
+    let metrics = sleeper.metrics();
+    assert_eq!(metrics.method("exec").unwrap().calls, 1);
+    // The queue wait starts when the timer fires, not when the message was sent
+    assert!(metrics.method("exec").unwrap().queue_wait.max() < Duration::from_millis(30));
+    assert!(metrics.method("send_at").is_none());
+    assert_eq!(sleeper.messages_processed(), 2);
+}
+
+#[test]
+fn worker_metrics_panics() {
+    let (handle, sleeper) = SleeperWorker::new();
+    sleeper.fail();
+    assert!(handle.join().is_err());
+
+    let metrics = sleeper.metrics();
+    assert_eq!(metrics.method("fail").unwrap().calls, 1);
+    assert_eq!(metrics.method("fail").unwrap().panics, 1);
+}
+
+#[test]
+fn worker_metrics_upgrade() {
+    let (handle, sleeper) = SleeperWorker::new();
+    sleeper.upgrade(|sleeper: Sleeper| Sleeper { naps: sleeper.naps + 10 });
+    assert_eq!(sleeper.naps(), 10);
+    sleeper.stop_thread();
+    handle.join().unwrap();
+
+    let metrics = sleeper.metrics();
+    assert_eq!(metrics.method("upgrade").unwrap().calls, 1);
+    assert_eq!(metrics.method("upgrade").unwrap().panics, 0);
+    assert_eq!(sleeper.messages_processed(), 2);
+
+    let (mut driver, sleeper) = SleeperWorker::new_manual();
+    sleeper.upgrade(|sleeper: Sleeper| Sleeper { naps: sleeper.naps + 10 });
+    assert_eq!(driver.step_all(), 1);
+    assert_eq!(sleeper.metrics().method("upgrade").unwrap().calls, 1);
+}
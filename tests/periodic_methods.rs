@@ -0,0 +1,94 @@
+use nano_services::*;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+struct Heartbeat {
+    beats: Arc<AtomicUsize>,
+    log: Vec<String>,
+}
+
+#[worker]
+impl Heartbeat {
+    pub fn new(beats: Arc<AtomicUsize>) -> Heartbeat {
+        Heartbeat {
+            beats,
+            log: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, entry: String) {
+        self.log.push(entry);
+    }
+
+    #[blocking_method]
+    pub fn log(&self) -> Vec<String> {
+        self.log.clone()
+    }
+
+    #[periodic(every_ms = 10)]
+    fn beat(&mut self) {
+        self.beats.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn worker_periodic_method() {
+    let beats = Arc::new(AtomicUsize::new(0));
+    let (handle, heartbeat) = HeartbeatWorker::new(Arc::clone(&beats));
+    std::thread::sleep(Duration::from_millis(100));
+    heartbeat.stop_thread();
+    handle.join().unwrap();
+
+    assert!(beats.load(Ordering::SeqCst) >= 3);
+}
+
+#[test]
+fn worker_periodic_method_between_messages() {
+    let beats = Arc::new(AtomicUsize::new(0));
+    let (handle, heartbeat) = HeartbeatWorker::new(Arc::clone(&beats));
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(100) {
+        heartbeat.push("busy".to_string());
+    }
+    heartbeat.stop_thread();
+    handle.join().unwrap();
+
+    assert!(beats.load(Ordering::SeqCst) >= 3);
+}
+
+#[test]
+fn worker_send_after() {
+    let beats = Arc::new(AtomicUsize::new(0));
+    let (handle, heartbeat) = HeartbeatWorker::new(Arc::clone(&beats));
+    heartbeat.send_after(Duration::from_millis(50), |heartbeat: &mut Heartbeat| {
+        heartbeat.push("later".to_string())
+    });
+    heartbeat.push("now".to_string());
+    assert_eq!(heartbeat.log(), vec!["now".to_string()]);
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(heartbeat.log(), vec!["now".to_string(), "later".to_string()]);
+    heartbeat.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn worker_send_at_ordering() {
+    let beats = Arc::new(AtomicUsize::new(0));
+    let (handle, heartbeat) = HeartbeatWorker::new(Arc::clone(&beats));
+    let now = Instant::now();
+    heartbeat.send_at(now + Duration::from_millis(40), |heartbeat: &mut Heartbeat| {
+        heartbeat.push("second".to_string())
+    });
+    heartbeat.send_at(now + Duration::from_millis(20), |heartbeat: &mut Heartbeat| {
+        heartbeat.push("first".to_string())
+    });
+    std::thread::sleep(Duration::from_millis(80));
+    assert_eq!(
+        heartbeat.log(),
+        vec!["first".to_string(), "second".to_string()]
+    );
+    heartbeat.stop_thread();
+    handle.join().unwrap();
+}
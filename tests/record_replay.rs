@@ -0,0 +1,98 @@
+#![cfg(feature = "record")]
+
+mod common;
+
+use common::TempPath;
+use nano_services::*;
+
+struct Ledger {
+    history: Vec<String>,
+    balance: i64,
+}
+
+#[worker(record)]
+impl Ledger {
+    pub fn new() -> Ledger {
+        Ledger {
+            history: Vec::new(),
+            balance: 0,
+        }
+    }
+
+    pub fn deposit(&mut self, amount: i64, memo: String) {
+        self.balance += amount;
+        self.history.push(format!("+{amount} {memo}"));
+    }
+
+    pub fn withdraw(&mut self, amount: i64) {
+        self.balance -= amount;
+        self.history.push(format!("-{amount}"));
+    }
+
+    pub fn note(&mut self, note: impl ToString + Send + 'static) {
+        self.history.push(note.to_string());
+    }
+
+    #[blocking_method]
+    pub fn balance(&self) -> i64 {
+        self.balance
+    }
+
+    #[blocking_method]
+    pub fn history(&self) -> Vec<String> {
+        self.history.clone()
+    }
+}
+
+#[test]
+fn record_messages() {
+    let path = TempPath::new("record_messages.jsonl");
+    let (handle, ledger) = LedgerWorker::new();
+    ledger.deposit(5, "before".to_string());
+    ledger.start_recording(&path).unwrap();
+    ledger.deposit(10, "pay".to_string());
+    let sender = ledger.clone();
+    std::thread::Builder::new()
+        .name("teller".to_string())
+        .spawn(move || sender.withdraw(3))
+        .unwrap()
+        .join()
+        .unwrap();
+    ledger.note("closure");
+    assert_eq!(ledger.balance(), 12);
+    ledger.stop_recording().unwrap();
+    ledger.withdraw(1);
+    ledger.stop_thread();
+    handle.join().unwrap();
+
+    let entries = record::read::<LedgerWorkerMessage>(&path).unwrap();
+    assert_eq!(entries.len(), 4);
+    assert!(matches!(&entries[0].message, LedgerWorkerMessage::Deposit { amount: 10, memo } if memo == "pay"));
+    assert!(matches!(entries[1].message, LedgerWorkerMessage::Withdraw { amount: 3 }));
+    assert!(matches!(entries[2].message, LedgerWorkerMessage::Note));
+    assert!(matches!(entries[3].message, LedgerWorkerMessage::Balance {}));
+    assert_eq!(entries[1].thread, "teller");
+    assert!(entries.windows(2).all(|x| x[0].timestamp_us <= x[1].timestamp_us));
+}
+
+#[test]
+fn replay_messages() {
+    let path = TempPath::new("replay_messages.jsonl");
+    let (handle, ledger) = LedgerWorker::new();
+    ledger.start_recording(&path).unwrap();
+    ledger.deposit(10, "pay".to_string());
+    ledger.withdraw(4);
+    ledger.exec(|ledger: &mut Ledger| ledger.balance *= 100);
+    ledger.deposit(1, "tip".to_string());
+    ledger.stop_recording().unwrap();
+    let recorded_history = ledger.history();
+    ledger.stop_thread();
+    handle.join().unwrap();
+
+    let (handle, ledger) = LedgerWorker::new();
+    assert_eq!(ledger.replay(&path).unwrap(), record::Replay { replayed: 3, skipped: 1 });
+    assert_eq!(ledger.history(), recorded_history);
+    assert_eq!(ledger.balance(), 7);
+    ledger.stop_thread();
+    handle.join().unwrap();
+}
@@ -0,0 +1,90 @@
+use nano_services::*;
+
+mod counter {
+    use nano_services::*;
+
+    pub struct Counter {
+        count: i32,
+    }
+
+    #[worker]
+    impl Counter {
+        pub fn new(count: i32) -> Counter {
+            Counter { count }
+        }
+
+        pub fn add(&mut self, i: i32) {
+            self.count += i;
+        }
+
+        #[blocking_method]
+        pub fn get(&self) -> i32 {
+            self.count
+        }
+    }
+}
+
+mod greeter {
+    use nano_services::*;
+
+    pub struct Greeter {}
+
+    #[worker]
+    impl Greeter {
+        pub fn new() -> Greeter {
+            Greeter {}
+        }
+
+        #[blocking_method]
+        pub fn greet(&self, name: String) -> String {
+            format!("hello {name}")
+        }
+    }
+}
+
+use counter::CounterWorker;
+use greeter::GreeterWorker;
+
+#[test]
+fn registry_get() {
+    let (handle, counter) = CounterWorker::spawn_named("registry_get", 5);
+    counter.add(1);
+
+    let found = Registry::get::<CounterWorker>("registry_get").unwrap();
+    assert_eq!(found.get(), 6);
+    assert!(Registry::contains("registry_get"));
+    assert!(Registry::names().contains(&"registry_get".to_string()));
+    assert!(Registry::get::<GreeterWorker>("registry_get").is_none());
+    assert!(Registry::get::<CounterWorker>("registry_missing").is_none());
+
+    counter.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn registry_removes_stopped_workers() {
+    let (handle, greeter) = GreeterWorker::spawn_named("registry_stopped");
+    assert_eq!(
+        Registry::get::<GreeterWorker>("registry_stopped")
+            .unwrap()
+            .greet("db".to_string()),
+        "hello db"
+    );
+    greeter.stop_thread();
+    handle.join().unwrap();
+    assert!(Registry::get::<GreeterWorker>("registry_stopped").is_none());
+    assert!(!Registry::contains("registry_stopped"));
+
+    // The name can be used again once the first worker has stopped
+    let (handle, greeter) = GreeterWorker::spawn_named("registry_stopped");
+    assert!(Registry::contains("registry_stopped"));
+    greeter.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+#[should_panic(expected = "A worker named \"registry_taken\" is already running")]
+fn registry_name_taken() {
+    let (_handle, _counter) = CounterWorker::spawn_named("registry_taken", 0);
+    CounterWorker::spawn_named("registry_taken", 1);
+}
@@ -0,0 +1,113 @@
+#![cfg(feature = "remote")]
+
+#[cfg(unix)]
+mod common;
+
+#[cfg(unix)]
+use common::TempPath;
+use nano_services::*;
+
+struct Inventory {
+    items: Vec<String>,
+}
+
+#[worker(remote)]
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory { items: Vec::new() }
+    }
+
+    pub fn add(&mut self, item: String) {
+        self.items.push(item);
+    }
+
+    #[blocking_method]
+    pub fn count(&self) -> usize {
+        self.items.len()
+    }
+
+    #[blocking_method]
+    pub fn take(&mut self, index: usize) -> Option<String> {
+        (index < self.items.len()).then(|| self.items.remove(index))
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn remote_methods() {
+    let path = TempPath::new("remote_methods.sock");
+    let (handle, inventory) = InventoryWorker::new();
+    inventory.serve_unix(&path).unwrap();
+
+    let remote = RemoteInventoryWorker::connect_unix(&path).unwrap();
+    remote.add("apple".to_string());
+    remote.add("pear".to_string());
+    assert_eq!(remote.count(), 2);
+    assert_eq!(remote.take(0), Some("apple".to_string()));
+    assert_eq!(remote.take(5), None);
+    assert_eq!(inventory.count(), 1);
+
+    inventory.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn remote_calls_from_many_threads() {
+    let path = TempPath::new("remote_threads.sock");
+    let (handle, inventory) = InventoryWorker::new();
+    inventory.serve_unix(&path).unwrap();
+
+    let remote = RemoteInventoryWorker::connect_unix(&path).unwrap();
+    let other = RemoteInventoryWorker::connect_unix(&path).unwrap();
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            let remote = if i % 2 == 0 { remote.clone() } else { other.clone() };
+            std::thread::spawn(move || {
+                for j in 0..25 {
+                    remote.add(format!("{i}-{j}"));
+                    assert!(remote.count() > j);
+                }
+            })
+        })
+        .collect();
+    threads.into_iter().for_each(|x| x.join().unwrap());
+    assert_eq!(remote.count(), 100);
+
+    inventory.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn remote_methods_over_tcp() {
+    let (handle, inventory) = InventoryWorker::new();
+    let (_, addr) = inventory.serve("127.0.0.1:0").unwrap();
+
+    let remote = RemoteInventoryWorker::connect(addr).unwrap();
+    remote.add("plum".to_string());
+    assert_eq!(remote.count(), 1);
+    assert_eq!(remote.take(0), Some("plum".to_string()));
+
+    let other = RemoteInventoryWorker::connect(addr).unwrap();
+    other.add("fig".to_string());
+    assert_eq!(other.count(), 1);
+
+    inventory.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn remote_stop_thread_is_ignored() {
+    let (handle, inventory) = InventoryWorker::new();
+    let (_, addr) = inventory.serve("127.0.0.1:0").unwrap();
+
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    remote::write_frame(&mut stream, &InventoryWorkerRequest::StopThread).unwrap();
+    remote::write_frame(&mut stream, &InventoryWorkerRequest::Count { correlation_id: 1 }).unwrap();
+    let response = remote::read_frame::<InventoryWorkerResponse>(&mut stream).unwrap();
+    assert!(matches!(response, Some(InventoryWorkerResponse::Count { correlation_id: 1, value: 0 })));
+    assert!(inventory.is_alive());
+
+    inventory.stop_thread();
+    handle.join().unwrap();
+}
@@ -0,0 +1,73 @@
+mod parser {
+    use nano_services::*;
+
+    pub struct Parser {}
+
+    #[worker]
+    impl Parser {
+        pub fn new() -> Parser {
+            Parser {}
+        }
+
+        #[blocking_method]
+        pub fn parse(&self, text: String) -> Vec<String> {
+            text.split_whitespace().map(str::to_lowercase).collect()
+        }
+    }
+}
+
+mod indexer {
+    use nano_services::*;
+
+    pub struct Indexer {
+        words: Vec<String>,
+    }
+
+    #[worker]
+    impl Indexer {
+        pub fn new() -> Indexer {
+            Indexer { words: Vec::new() }
+        }
+
+        pub fn index(&mut self, words: Vec<String>) {
+            self.words.extend(words);
+        }
+
+        #[blocking_method]
+        pub fn words(&self) -> Vec<String> {
+            self.words.clone()
+        }
+    }
+}
+
+use indexer::IndexerWorker;
+use parser::ParserWorker;
+
+#[test]
+fn reply_to_another_worker() {
+    let (parser_handle, parser) = ParserWorker::new();
+    let (indexer_handle, indexer) = IndexerWorker::new();
+    parser.parse_reply_to("Hello World".to_string(), indexer.clone(), IndexerWorker::index);
+    parser.parse_reply_to("again".to_string(), indexer.clone(), IndexerWorker::index);
+
+    // The parser sends to the indexer before handling the next message
+    assert_eq!(parser.parse(String::new()), Vec::<String>::new());
+    assert_eq!(indexer.words(), ["hello", "world", "again"]);
+
+    parser.stop_thread();
+    parser_handle.join().unwrap();
+    indexer.stop_thread();
+    indexer_handle.join().unwrap();
+}
+
+#[test]
+fn reply_to_channel() {
+    let (handle, parser) = ParserWorker::new();
+    let (send, recv) = std::sync::mpsc::channel();
+    parser.parse_reply_to("a b".to_string(), send, |send, words| {
+        send.send(words.len()).unwrap()
+    });
+    assert_eq!(recv.recv().unwrap(), 2);
+    parser.stop_thread();
+    handle.join().unwrap();
+}
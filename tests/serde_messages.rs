@@ -0,0 +1,78 @@
+#![cfg(feature = "serde")]
+
+use nano_services::*;
+
+struct Tally {
+    total: i64,
+}
+
+#[worker(serde)]
+impl Tally {
+    pub fn new() -> Tally {
+        Tally { total: 0 }
+    }
+
+    pub fn add(&mut self, amount: i64, times: u32) {
+        self.total += amount * i64::from(times);
+    }
+
+    #[blocking_method]
+    pub fn total(&self) -> i64 {
+        self.total
+    }
+
+    #[blocking_method]
+    pub fn reset(&mut self) {
+        self.total = 0;
+    }
+}
+
+#[test]
+fn request_round_trip() {
+    let request = TallyWorkerRequest::Add {
+        amount: 3,
+        times: 2,
+    };
+    let json = serde_json::to_string(&request).unwrap();
+    assert_eq!(json, r#"{"add":{"amount":3,"times":2}}"#);
+    assert!(matches!(
+        serde_json::from_str(&json).unwrap(),
+        TallyWorkerRequest::Add {
+            amount: 3,
+            times: 2
+        }
+    ));
+    assert!(matches!(
+        serde_json::from_str(r#""stop_thread""#).unwrap(),
+        TallyWorkerRequest::StopThread
+    ));
+}
+
+#[test]
+fn handle_requests() {
+    let (handle, tally) = TallyWorker::new();
+    let requests = [
+        r#"{"add":{"amount":3,"times":2}}"#,
+        r#"{"total":{"correlation_id":7}}"#,
+        r#"{"reset":{"correlation_id":8}}"#,
+        r#"{"total":{"correlation_id":9}}"#,
+    ];
+    let responses: Vec<String> = requests
+        .iter()
+        .filter_map(|request| tally.handle_request(serde_json::from_str(request).unwrap()))
+        .map(|response| serde_json::to_string(&response).unwrap())
+        .collect();
+    assert_eq!(
+        responses,
+        [
+            r#"{"total":{"correlation_id":7,"value":6}}"#,
+            r#"{"reset":{"correlation_id":8,"value":null}}"#,
+            r#"{"total":{"correlation_id":9,"value":0}}"#,
+        ]
+    );
+
+    assert!(tally
+        .handle_request(TallyWorkerRequest::StopThread)
+        .is_none());
+    handle.join().unwrap();
+}
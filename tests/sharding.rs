@@ -0,0 +1,94 @@
+use nano_services::*;
+
+use std::collections::HashMap;
+
+struct Cache {
+    shard: usize,
+    entries: HashMap<String, Vec<u32>>,
+}
+
+#[worker]
+impl Cache {
+    pub fn new(shard: usize) -> Cache {
+        Cache {
+            shard,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, #[shard_key] customer: String, value: u32) {
+        self.entries.entry(customer).or_default().push(value);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    #[blocking_method]
+    pub fn get(&self, #[shard_key] customer: String) -> Vec<u32> {
+        self.entries.get(&customer).cloned().unwrap_or_default()
+    }
+
+    #[blocking_method]
+    pub fn owner(&self, #[shard_key] customer: String) -> (usize, bool) {
+        (self.shard, self.entries.contains_key(&customer))
+    }
+
+    #[blocking_method]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[streaming]
+    pub fn values(&self, #[shard_key] customer: String, sink: Sink<u32>) {
+        for value in self.entries.get(&customer).into_iter().flatten() {
+            let _ = sink.send(*value);
+        }
+    }
+}
+
+fn stop(handles: Vec<std::thread::JoinHandle<()>>, shards: CacheWorkerShards) {
+    shards.stop_thread();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn same_key_same_worker() {
+    let (handles, shards) = CacheWorkerShards::new(4, |index| (index,));
+    assert_eq!(shards.shard_count(), 4);
+    let customers: Vec<String> = (0..20).map(|x| format!("customer-{x}")).collect();
+    for value in 0..5 {
+        for customer in &customers {
+            shards.push(customer.clone(), value);
+        }
+    }
+
+    for customer in &customers {
+        assert_eq!(shards.get(customer.clone()), [0, 1, 2, 3, 4]);
+        assert_eq!(shards.values(customer.clone()).iter().collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+        let (owner, found) = shards.owner(customer.clone());
+        assert!(found);
+        assert_eq!(shards.shard(customer).owner(customer.clone()), (owner, true));
+    }
+    let lens: Vec<usize> = shards.workers().iter().map(|x| x.len()).collect();
+    assert_eq!(lens.iter().sum::<usize>(), customers.len());
+    assert!(lens.iter().filter(|x| **x > 0).count() > 1);
+    stop(handles, shards);
+}
+
+#[test]
+fn unkeyed_methods_through_workers() {
+    let (handles, shards) = CacheWorkerShards::new(2, |index| (index,));
+    shards.push("a".to_string(), 1);
+    shards.push("b".to_string(), 2);
+    shards.workers().iter().for_each(|x| x.clear());
+    assert!(shards.get("a".to_string()).is_empty());
+    assert!(shards.get("b".to_string()).is_empty());
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    shards.get_then("a".to_string(), move |values| tx.send(values).unwrap());
+    assert!(rx.recv().unwrap().is_empty());
+    stop(handles, shards);
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "snapshot")]
+
+mod common;
+
+mod inventory {
+    use nano_services::serde::{Deserialize, Serialize};
+    use nano_services::*;
+
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "nano_services::serde")]
+    pub struct Inventory {
+        stock: BTreeMap<String, u32>,
+    }
+
+    #[worker(snapshot)]
+    impl Inventory {
+        pub fn new() -> Inventory {
+            Inventory { stock: BTreeMap::new() }
+        }
+
+        pub fn add(&mut self, item: String, count: u32) {
+            *self.stock.entry(item).or_default() += count;
+        }
+
+        #[blocking_method]
+        pub fn count(&self, item: String) -> u32 {
+            self.stock.get(&item).copied().unwrap_or_default()
+        }
+    }
+}
+
+mod grid {
+    use nano_services::serde::{Deserialize, Serialize};
+    use nano_services::*;
+
+    use std::collections::HashMap;
+
+    // JSON objects only have string keys, so this can't be serialized once it has a cell
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "nano_services::serde")]
+    pub struct Grid {
+        cells: HashMap<(i32, i32), i32>,
+    }
+
+    #[worker(snapshot)]
+    impl Grid {
+        pub fn new() -> Grid {
+            Grid { cells: HashMap::new() }
+        }
+
+        pub fn set(&mut self, x: i32, y: i32, value: i32) {
+            self.cells.insert((x, y), value);
+        }
+
+        #[blocking_method]
+        pub fn get(&self, x: i32, y: i32) -> Option<i32> {
+            self.cells.get(&(x, y)).copied()
+        }
+    }
+}
+
+use common::TempPath;
+use grid::GridWorker;
+use inventory::InventoryWorker;
+
+#[test]
+fn snapshot_after_queued_messages() {
+    let (handle, inventory) = InventoryWorker::new();
+    inventory.add("apple".to_string(), 3);
+    inventory.add("pear".to_string(), 1);
+    let snapshot = inventory.snapshot().unwrap();
+    inventory.add("apple".to_string(), 10);
+    assert_eq!(inventory.count("apple".to_string()), 13);
+    inventory.stop_thread();
+    handle.join().unwrap();
+
+    let (handle, restored) = InventoryWorker::restore(&snapshot).unwrap();
+    assert_eq!(restored.count("apple".to_string()), 3);
+    assert_eq!(restored.count("pear".to_string()), 1);
+    restored.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn checkpoint_to_disk() {
+    let path = TempPath::new("checkpoint.json");
+    let (handle, inventory) = InventoryWorker::new();
+    inventory.add("plum".to_string(), 7);
+    std::fs::write(&path, inventory.snapshot().unwrap()).unwrap();
+    inventory.stop_thread();
+    handle.join().unwrap();
+
+    let (handle, restored) = InventoryWorker::restore(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(restored.count("plum".to_string()), 7);
+    restored.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn restore_invalid_snapshot() {
+    assert!(InventoryWorker::restore(b"not a snapshot").is_err());
+}
+
+#[test]
+fn snapshot_unserializable_state() {
+    let (handle, grid) = GridWorker::new();
+    grid.set(1, 2, 3);
+    let err = grid.snapshot().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(grid.get(1, 2), Some(3));
+    grid.stop_thread();
+    handle.join().unwrap();
+}
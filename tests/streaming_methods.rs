@@ -0,0 +1,88 @@
+use nano_services::*;
+
+use std::time::Duration;
+
+struct Table {
+    rows: Vec<String>,
+}
+
+#[worker]
+impl Table {
+    pub fn new(rows: Vec<String>) -> Table {
+        Table { rows }
+    }
+
+    pub fn insert(&mut self, row: String) {
+        self.rows.push(row);
+    }
+
+    #[streaming]
+    pub fn scan(&self, prefix: String, sink: Sink<String>) {
+        for row in self.rows.iter().filter(|x| x.starts_with(&prefix)) {
+            if sink.send(row.clone()).is_err() {
+                break;
+            }
+        }
+    }
+
+    #[streaming(buffer = 2)]
+    pub fn count_to(&mut self, n: usize, sink: Sink<usize>) {
+        for i in 0..n {
+            if sink.send(i).is_err() {
+                self.rows.push(format!("stopped at {i}"));
+                return;
+            }
+        }
+    }
+
+    #[blocking_method]
+    pub fn rows(&self) -> Vec<String> {
+        self.rows.clone()
+    }
+}
+
+fn table() -> (std::thread::JoinHandle<()>, TableWorker) {
+    TableWorker::new(vec![
+        "apple".to_string(),
+        "avocado".to_string(),
+        "banana".to_string(),
+    ])
+}
+
+#[test]
+fn streaming_method() {
+    let (handle, table) = table();
+    let rows: Vec<String> = table.scan("a".to_string()).into_iter().collect();
+    assert_eq!(rows, ["apple", "avocado"]);
+    assert_eq!(table.scan("c".to_string()).iter().count(), 0);
+    table.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn streaming_keeps_message_order() {
+    let (handle, table) = table();
+    table.insert("apricot".to_string());
+    let stream = table.scan("ap".to_string());
+    table.insert("april".to_string());
+    assert_eq!(stream.iter().collect::<Vec<_>>(), ["apple", "apricot"]);
+    table.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn streaming_backpressure() {
+    let (handle, table) = table();
+    let stream = table.count_to(100);
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(stream.len(), 2);
+    assert_eq!(stream.recv().unwrap(), 0);
+    assert_eq!(stream.recv().unwrap(), 1);
+
+    // Dropping the stream stops the method the next time it sends (at most 2 items later)
+    drop(stream);
+    let stopped = table.rows().pop().unwrap();
+    assert!(["stopped at 2", "stopped at 3", "stopped at 4"].contains(&stopped.as_str()));
+    table.stop_thread();
+    handle.join().unwrap();
+}
@@ -0,0 +1,157 @@
+#![cfg(feature = "tracing")]
+
+use nano_services::tracing::{self, info_span};
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+mod back {
+    use nano_services::*;
+
+    pub struct Opaque;
+
+    pub struct Back {}
+
+    #[worker]
+    impl Back {
+        pub fn new() -> Back {
+            Back {}
+        }
+
+        pub fn greet(&self, name: String, times: u32) {
+            let _ = (name, times);
+        }
+
+        #[blocking_method]
+        pub fn take(&self, opaque: Opaque) -> bool {
+            let _ = opaque;
+            true
+        }
+    }
+}
+
+mod front {
+    use super::back::BackWorker;
+    use nano_services::*;
+
+    pub struct Front {
+        pub back: BackWorker,
+    }
+
+    #[worker]
+    impl Front {
+        pub fn new(back: BackWorker) -> Front {
+            Front { back }
+        }
+
+        pub fn forward(&self, name: String) {
+            self.back.greet(name, 2);
+        }
+    }
+}
+
+use back::{BackWorker, Opaque};
+use front::FrontWorker;
+
+#[derive(Clone, Debug, PartialEq)]
+struct SpanRecord {
+    name: String,
+    parent: Option<String>,
+    fields: String,
+}
+
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<SpanRecord>>>,
+}
+
+struct FieldRecorder<'a>(&'a mut String);
+
+impl Visit for FieldRecorder<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        write!(self.0, "{}={:?} ", field.name(), value).unwrap();
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let mut fields = String::new();
+        attrs.record(&mut FieldRecorder(&mut fields));
+        self.spans.lock().unwrap().push(SpanRecord {
+            name: span.name().to_string(),
+            parent: span.parent().map(|x| x.name().to_string()),
+            fields: fields.trim_end().to_string(),
+        });
+    }
+}
+
+// Workers run on their own threads, so the subscriber has to be global
+fn recorded_spans() -> Arc<Mutex<Vec<SpanRecord>>> {
+    static RECORDER: OnceLock<Recorder> = OnceLock::new();
+    let recorder = RECORDER.get_or_init(|| {
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::set_global_default(subscriber).unwrap();
+        recorder
+    });
+    Arc::clone(&recorder.spans)
+}
+
+fn find_span(spans: &Arc<Mutex<Vec<SpanRecord>>>, name: &str, parent: &str) -> Option<SpanRecord> {
+    let spans = spans.lock().unwrap();
+    spans
+        .iter()
+        .find(|x| x.name == name && x.parent.as_deref() == Some(parent))
+        .cloned()
+}
+
+#[test]
+fn worker_span_per_message() {
+    let spans = recorded_spans();
+    let (handle, back) = BackWorker::new();
+    info_span!("single_request").in_scope(|| {
+        back.greet("bob".to_string(), 1);
+        assert!(back.take(Opaque));
+    });
+    back.stop_thread();
+    handle.join().unwrap();
+
+    let greet = find_span(&spans, "Back::greet", "single_request").unwrap();
+    assert_eq!(greet.fields, "name=\"bob\" times=1");
+    let take = find_span(&spans, "Back::take", "single_request").unwrap();
+    assert_eq!(take.fields, "opaque=\"<not Debug>\"");
+}
+
+#[test]
+fn worker_span_across_services() {
+    let spans = recorded_spans();
+    let (back_handle, back) = BackWorker::new();
+    let (front_handle, front) = FrontWorker::new(back.clone());
+    info_span!("chained_request").in_scope(|| front.forward("alice".to_string()));
+    front.stop_thread();
+    front_handle.join().unwrap();
+    back.stop_thread();
+    back_handle.join().unwrap();
+
+    let forward = find_span(&spans, "Front::forward", "chained_request").unwrap();
+    assert_eq!(forward.fields, "name=\"alice\"");
+    let greet = find_span(&spans, "Back::greet", "Front::forward").unwrap();
+    assert_eq!(greet.fields, "name=\"alice\" times=2");
+}
+
+#[test]
+fn worker_span_for_upgrade() {
+    let spans = recorded_spans();
+    let (handle, back) = BackWorker::new();
+    info_span!("upgrade_request").in_scope(|| back.upgrade(|back: back::Back| back));
+    back.stop_thread();
+    handle.join().unwrap();
+
+    assert!(find_span(&spans, "Back::upgrade", "upgrade_request").is_some());
+}
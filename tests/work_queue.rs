@@ -0,0 +1,80 @@
+use nano_services::*;
+
+use std::collections::HashSet;
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread::ThreadId;
+
+struct Jobs {
+    barrier: Arc<Barrier>,
+    threads: Arc<Mutex<HashSet<ThreadId>>>,
+    done: u32,
+}
+
+#[worker(consumers = 4)]
+impl Jobs {
+    pub fn new(barrier: Arc<Barrier>, threads: Arc<Mutex<HashSet<ThreadId>>>) -> Jobs {
+        Jobs {
+            barrier,
+            threads,
+            done: 0,
+        }
+    }
+
+    // Only returns once every consumer is running it at the same time
+    pub fn meet(&mut self) {
+        self.threads.lock().unwrap().insert(std::thread::current().id());
+        self.barrier.wait();
+        self.done += 1;
+    }
+
+    #[blocking_method]
+    pub fn double(&self, value: u32) -> u32 {
+        value * 2
+    }
+
+    // Waits on another consumer of the same worker
+    #[blocking_method]
+    pub fn quadruple(&self, value: u32) -> u32 {
+        let this = Registry::get::<JobsWorker>("work_queue_jobs").unwrap();
+        this.double(this.double(value))
+    }
+}
+
+fn new_jobs() -> (std::thread::JoinHandle<()>, JobsWorker, Arc<Mutex<HashSet<ThreadId>>>) {
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+    let (handle, jobs) = JobsWorker::new(Arc::new(Barrier::new(4)), Arc::clone(&threads));
+    (handle, jobs, threads)
+}
+
+#[test]
+fn consumers_share_the_mailbox() {
+    let (handle, jobs, threads) = new_jobs();
+    for _ in 0..4 {
+        jobs.meet();
+    }
+    assert!(jobs.is_alive());
+    jobs.stop_thread();
+    handle.join().unwrap();
+    assert!(!jobs.is_alive());
+    assert_eq!(jobs.messages_processed(), 4);
+    assert_eq!(threads.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn blocking_calls_between_consumers() {
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+    let (handle, jobs) = JobsWorker::spawn_named("work_queue_jobs", Arc::new(Barrier::new(4)), threads);
+    assert_eq!(jobs.double(3), 6);
+    assert_eq!(jobs.quadruple(3), 12);
+    jobs.stop_thread();
+    handle.join().unwrap();
+}
+
+#[test]
+fn consumer_panic_reaches_handle() {
+    let (handle, jobs, _) = new_jobs();
+    jobs.exec(|_: &mut Jobs| panic!("consumer failed"));
+    jobs.stop_thread();
+    let payload = handle.join().unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"consumer failed"));
+}
@@ -0,0 +1,139 @@
+use nano_services::*;
+
+use std::sync::mpsc;
+
+struct Shard {
+    id: usize,
+    total: u32,
+    rate: u32,
+    notes: Vec<String>,
+}
+
+#[worker]
+impl Shard {
+    pub fn new(id: usize, rate: u32) -> Shard {
+        Shard {
+            id,
+            total: 0,
+            rate,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, amount: u32) {
+        self.total += amount * self.rate;
+    }
+
+    pub fn set_rate(&mut self, rate: u32) {
+        self.rate = rate;
+    }
+
+    pub fn note(&mut self, note: impl ToString + Send + 'static) {
+        self.notes.push(note.to_string());
+    }
+
+    #[blocking_method]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    #[blocking_method]
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    #[blocking_method]
+    pub fn notes(&self) -> Vec<String> {
+        self.notes.clone()
+    }
+
+    #[streaming(buffer = 2)]
+    pub fn ids(&self, count: usize, sink: Sink<usize>) {
+        for _ in 0..count {
+            let _ = sink.send(self.id);
+        }
+    }
+}
+
+fn stop(handles: Vec<std::thread::JoinHandle<()>>, pool: ShardWorkerPool) {
+    pool.stop_thread();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn round_robin_calls() {
+    let (handles, pool) = ShardWorkerPool::new(3, |index| (index, 1));
+    assert_eq!(pool.pool_size(), 3);
+    assert_eq!((0..6).map(|_| pool.id()).collect::<Vec<_>>(), [0, 1, 2, 0, 1, 2]);
+    for amount in 1..=6 {
+        pool.add(amount);
+    }
+    let totals: Vec<u32> = pool.workers().iter().map(|x| x.total()).collect();
+    assert_eq!(totals, [1 + 4, 2 + 5, 3 + 6]);
+    pool.note("first");
+    assert_eq!(pool.ids(2).iter().collect::<Vec<_>>(), [1, 1]);
+    assert_eq!(pool.workers()[0].notes(), ["first"]);
+    stop(handles, pool);
+}
+
+#[test]
+fn least_loaded_calls() {
+    let (handles, pool) = ShardWorkerPool::new(3, |index| (index, 1));
+    let pool = pool.with_routing(Routing::LeastLoaded);
+
+    // Keeps the first worker busy, with messages waiting in its mailbox
+    let (send_started, recv_started) = mpsc::channel();
+    let (send_release, recv_release) = mpsc::channel::<()>();
+    pool.workers()[0].exec(move |_: &mut Shard| {
+        send_started.send(()).unwrap();
+        recv_release.recv().unwrap();
+    });
+    recv_started.recv().unwrap();
+    for _ in 0..3 {
+        pool.workers()[0].add(100);
+    }
+
+    for _ in 0..4 {
+        pool.add(1);
+    }
+    send_release.send(()).unwrap();
+    let totals: Vec<u32> = pool.workers().iter().map(|x| x.total()).collect();
+    assert_eq!(totals[0], 300);
+    assert_eq!(totals[1] + totals[2], 4);
+    stop(handles, pool);
+}
+
+#[test]
+fn broadcast_to_every_worker() {
+    let (handles, pool) = ShardWorkerPool::new(4, |index| (index, 1));
+    pool.broadcast_set_rate(10u32);
+    for _ in 0..4 {
+        pool.add(2);
+    }
+    assert!(pool.workers().iter().all(|x| x.total() == 20));
+    stop(handles, pool);
+}
+
+#[test]
+fn continuations_from_pool() {
+    let (handles, pool) = ShardWorkerPool::new(2, |index| (index * 10, 1));
+    let (send, recv) = mpsc::channel();
+    for _ in 0..2 {
+        let send = send.clone();
+        pool.id_then(move |id| send.send(id).unwrap());
+    }
+    let mut ids = vec![recv.recv().unwrap(), recv.recv().unwrap()];
+    ids.sort();
+    assert_eq!(ids, [0, 10]);
+    stop(handles, pool);
+}
+
+#[test]
+fn pool_stops_every_worker() {
+    let (handles, pool) = ShardWorkerPool::new(3, |index| (index, 1));
+    let workers = pool.workers().to_vec();
+    stop(handles, pool);
+    assert!(workers.iter().all(|x| !x.is_alive()));
+}